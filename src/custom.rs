@@ -0,0 +1,270 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::game::Settings;
+use crate::gravity::{to_gravity_curve, FRAME_DURATION};
+use crate::mode::{Mode, SPRINT_LINES};
+use crate::primitives::{to_line_gravity, to_progression, to_randomizer, to_scoring, ARENA_DIMS};
+
+// where modes given by name are looked up
+const MODES_DIR: &str = "modes";
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Goal {
+    Endless,
+    Marathon { level: u32, lines: u32 },
+    Sprint(u32),
+    // seconds
+    Ultra(u64),
+    // clear all of the starting garbage
+    Dig,
+    // seconds before the first garbage row rises
+    Survival(u32),
+}
+
+// a mode built from a file. anything left out falls back to the command line settings
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CustomMode {
+    pub name: String,
+    pub goal: Goal,
+    pub randomizer: Option<String>,
+    // whether the next piece is shown
+    pub preview: Option<bool>,
+    pub gravity: Option<String>,
+    // pairs of a starting level and the gravity from there on, in 1/256ths of a row per frame
+    pub gravity_table: Option<Vec<(u32, u32)>>,
+    // frames
+    pub lock_delay: Option<u32>,
+    pub line_gravity: Option<String>,
+    pub items: Option<bool>,
+    pub scoring: Option<String>,
+    pub progression: Option<String>,
+    pub start_level: Option<u32>,
+    #[serde(default)]
+    pub garbage: u32,
+    #[serde(default)]
+    pub messiness: u32,
+}
+
+impl CustomMode {
+    pub fn mode(&self) -> Mode {
+        match self.goal {
+            Goal::Endless => Mode::Endless,
+            Goal::Marathon { level, lines } => Mode::Marathon { level, lines },
+            Goal::Sprint(lines) => Mode::Sprint { lines },
+            Goal::Ultra(secs) => Mode::Ultra {
+                duration: Duration::from_secs(secs),
+            },
+            Goal::Dig => Mode::Dig {
+                rows: self.garbage,
+                messiness: self.messiness,
+            },
+            Goal::Survival(secs) => Mode::Survival {
+                interval: secs * 1000 / FRAME_DURATION as u32,
+            },
+        }
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.mode = self.mode();
+        if let Some(randomizer) = &self.randomizer {
+            settings.randomizer = to_randomizer(randomizer);
+        }
+        if let Some(preview) = self.preview {
+            settings.preview = preview;
+        }
+        if let Some(gravity) = &self.gravity {
+            settings.gravity = to_gravity_curve(gravity);
+        }
+        if let Some(table) = &self.gravity_table {
+            settings.gravity_table = table.clone();
+        }
+        if let Some(lock_delay) = self.lock_delay {
+            settings.lock_delay = lock_delay;
+        }
+        if let Some(line_gravity) = &self.line_gravity {
+            settings.line_gravity = to_line_gravity(line_gravity);
+        }
+        if let Some(items) = self.items {
+            settings.items = items;
+        }
+        if let Some(scoring) = &self.scoring {
+            settings.scoring = to_scoring(scoring);
+        }
+        if let Some(progression) = &self.progression {
+            settings.progression = to_progression(progression);
+        }
+        if let Some(start_level) = self.start_level {
+            settings.start_level = start_level;
+        }
+        if self.goal != Goal::Dig {
+            settings.garbage = (self.garbage, self.messiness);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let check = |field: &str, value: &Option<String>, allowed: &[&str]| match value {
+            Some(value) if !allowed.contains(&value.as_str()) => Err(format!(
+                "mode \"{}\" has {} {}, try one of: {}",
+                self.name,
+                field,
+                value,
+                allowed.join(", ")
+            )),
+            _ => Ok(()),
+        };
+        check("randomizer", &self.randomizer, &["random", "bag"])?;
+        check("gravity", &self.gravity, &["nes", "guideline", "20g"])?;
+        check("line gravity", &self.line_gravity, &["naive", "cascade"])?;
+        check("scoring", &self.scoring, &["nes", "guideline"])?;
+        check(
+            "progression",
+            &self.progression,
+            &["fixed", "nes", "variable"],
+        )?;
+
+        match self.goal {
+            Goal::Sprint(lines) if !SPRINT_LINES.contains(&lines) => {
+                let lengths = SPRINT_LINES
+                    .iter()
+                    .map(|lines| lines.to_string())
+                    .collect::<Vec<String>>();
+                return Err(format!(
+                    "mode \"{}\" has a sprint of {} lines, try one of: {}",
+                    self.name,
+                    lines,
+                    lengths.join(", ")
+                ));
+            }
+            Goal::Marathon { level: 0, .. }
+            | Goal::Marathon { lines: 0, .. }
+            | Goal::Ultra(0)
+            | Goal::Survival(0) => {
+                return Err(format!("mode \"{}\" has an empty goal", self.name));
+            }
+            _ => (),
+        }
+        if self.gravity.is_some() && self.gravity_table.is_some() {
+            return Err(format!(
+                "mode \"{}\" has both a gravity curve and a gravity table",
+                self.name
+            ));
+        }
+        if let Some(table) = &self.gravity_table {
+            if table.first().map(|(level, _)| *level) != Some(0) {
+                return Err(format!(
+                    "the gravity table of mode \"{}\" has to start at level 0",
+                    self.name
+                ));
+            }
+            if table.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                return Err(format!(
+                    "the gravity table of mode \"{}\" is not sorted by level",
+                    self.name
+                ));
+            }
+            if table.iter().any(|(_, internal)| *internal > 20 * 256) {
+                return Err(format!(
+                    "the gravity table of mode \"{}\" goes past 20G",
+                    self.name
+                ));
+            }
+        }
+        if self
+            .start_level
+            .is_some_and(|level| level == 0 || level > 25)
+        {
+            return Err(format!("mode \"{}\" has a wrong start level", self.name));
+        }
+        if self.lock_delay == Some(0) {
+            return Err(format!("mode \"{}\" has no lock delay", self.name));
+        }
+        if self.garbage as usize >= ARENA_DIMS.row {
+            return Err(format!("mode \"{}\" has too much garbage", self.name));
+        }
+        if self.goal == Goal::Dig && self.garbage == 0 {
+            return Err(format!("mode \"{}\" has no garbage to dig", self.name));
+        }
+        if self.messiness > 100 {
+            return Err(format!("mode \"{}\" has a messiness above 100", self.name));
+        }
+        Ok(())
+    }
+}
+
+// loads a mode from a file, or by name from the modes directory
+pub fn load_custom_mode(name: &str) -> Result<CustomMode, String> {
+    let path = match Path::new(name).is_file() {
+        true => name.to_string(),
+        false => format!("{}/{}.toml", MODES_DIR, name),
+    };
+    let contents = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let custom: CustomMode = toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    custom.validate().map_err(|e| format!("{}: {}", path, e))?;
+    Ok(custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<(), String> {
+        let custom: CustomMode = toml::from_str(contents).map_err(|e| e.to_string())?;
+        custom.validate()
+    }
+
+    #[test]
+    fn accepts_the_bundled_modes() {
+        assert_eq!(parse(include_str!("../modes/bag-sprint.toml")), Ok(()));
+        assert_eq!(parse(include_str!("../modes/cheese.toml")), Ok(()));
+    }
+
+    #[test]
+    fn sprints_are_limited_to_the_command_line_lengths() {
+        for lines in SPRINT_LINES.iter() {
+            let contents = format!("name = \"s\"\ngoal = {{ sprint = {} }}", lines);
+            assert_eq!(parse(&contents), Ok(()));
+        }
+        assert!(parse("name = \"s\"\ngoal = { sprint = 150 }").is_err());
+        assert!(parse("name = \"s\"\ngoal = { sprint = 0 }").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_goals() {
+        for goal in [
+            "{ marathon = { level = 0, lines = 150 } }",
+            "{ marathon = { level = 15, lines = 0 } }",
+            "{ ultra = 0 }",
+            "{ survival = 0 }",
+        ]
+        .iter()
+        {
+            assert!(parse(&format!("name = \"e\"\ngoal = {}", goal)).is_err());
+        }
+        assert_eq!(
+            parse("name = \"m\"\ngoal = { marathon = { level = 15, lines = 150 } }"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_fields() {
+        assert!(parse("name = \"w\"\ngoal = \"endless\"\nwidth = 10").is_err());
+        assert!(parse("name = \"h\"\ngoal = \"endless\"\nhold = false").is_err());
+        assert!(parse("name = \"p\"\ngoal = \"endless\"\npreview = 1").is_err());
+        assert_eq!(
+            parse("name = \"p\"\ngoal = \"endless\"\npreview = false"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_dig_without_garbage() {
+        assert!(parse("name = \"d\"\ngoal = \"dig\"").is_err());
+        assert_eq!(parse("name = \"d\"\ngoal = \"dig\"\ngarbage = 5"), Ok(()));
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{env, fs, mem, ptr};
+
+use crate::gravity::FRAME_DURATION;
+use crate::mode::Mode;
+
+const HISTORY_FILE: &str = ".tetris-rs-daily.toml";
+
+// the local date as yyyy-mm-dd
+pub fn today() -> Result<String, String> {
+    // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes to the `tm` it is
+    // given, which is plain data and valid when zeroed
+    let local = unsafe {
+        let now = libc::time(ptr::null_mut());
+        let mut local: libc::tm = mem::zeroed();
+        if libc::localtime_r(&now, &mut local).is_null() {
+            return Err("the local time is unavailable".to_string());
+        }
+        local
+    };
+    Ok(format!(
+        "{:04}-{:02}-{:02}",
+        local.tm_year + 1900,
+        local.tm_mon + 1,
+        local.tm_mday
+    ))
+}
+
+// fnv-1a, so the seed for a date is the same on every machine and build
+pub fn date_seed(date: &str) -> u64 {
+    date.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// the goal of the day, picked from the seed
+pub fn daily_mode(seed: u64) -> Mode {
+    let param = seed >> 8;
+    match seed % 4 {
+        0 => Mode::Sprint {
+            lines: [20, 40][(param % 2) as usize],
+        },
+        1 => Mode::Ultra {
+            duration: Duration::from_secs([60, 120, 180][(param % 3) as usize]),
+        },
+        2 => Mode::Dig {
+            rows: 5 + (param % 11) as u32,
+            messiness: (param / 11 % 5) as u32 * 20,
+        },
+        _ => Mode::Survival {
+            interval: (5 + (param % 6) as u32) * 1000 / FRAME_DURATION as u32,
+        },
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub mode: String,
+    pub finished: bool,
+    pub score: u64,
+    pub lines: u32,
+    pub time: String,
+}
+
+fn history_path() -> Result<PathBuf, String> {
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(HISTORY_FILE))
+        .ok_or_else(|| "HOME is not set".to_string())
+}
+
+// adds a result to the history under its date, returning the number of attempts on that date
+pub fn record(date: &str, result: DailyResult) -> Result<usize, String> {
+    let path = history_path()?;
+    let mut history: BTreeMap<String, Vec<DailyResult>> = match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|e| e.to_string())?,
+        Err(_) => BTreeMap::new(),
+    };
+    let results = history.entry(date.to_string()).or_default();
+    results.push(result);
+    let attempts = results.len();
+    let contents = toml::to_string(&history).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| e.to_string())?;
+    Ok(attempts)
+}
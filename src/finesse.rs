@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+
+use crate::primitives::{Coord, Direction, ARENA_DIMS, ARENA_TL};
+use crate::shape::Shape;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    Left,
+    Right,
+    Rotate,
+}
+
+// every position and rotation a freshly spawned shape can reach on an empty row, along with
+// the fewest inputs to get there. rotations kick off the walls the same way as in the game
+pub fn placements(spawn: Shape) -> Vec<(Shape, Vec<Input>)> {
+    let mut reached = vec![(spawn, Vec::new())];
+    let mut queue = VecDeque::from(vec![(spawn, Vec::new())]);
+    while let Some((shape, inputs)) = queue.pop_front() {
+        for input in [Input::Left, Input::Right, Input::Rotate] {
+            let next = match apply(shape, input) {
+                Some(next) => next,
+                None => continue,
+            };
+            let is_new = reached.iter().all(|(other, _)| {
+                other.rotations() != next.rotations() || other.tl_coords() != next.tl_coords()
+            });
+            if is_new {
+                let mut next_inputs = inputs.clone();
+                next_inputs.push(input);
+                reached.push((next, next_inputs.clone()));
+                queue.push_back((next, next_inputs));
+            }
+        }
+    }
+    reached
+}
+
+// the fewest inputs that line a shape up above the given blocks, so that a hard drop lands on them
+pub fn finesse_path(spawn: Shape, target: &[Coord]) -> Option<Vec<Input>> {
+    placements(spawn)
+        .into_iter()
+        .find(|(shape, _)| is_above(shape, target))
+        .map(|(_, inputs)| inputs)
+}
+
+pub fn format_inputs(inputs: &[Input]) -> String {
+    if inputs.is_empty() {
+        return "drop".to_string();
+    }
+    inputs
+        .iter()
+        .map(|input| match input {
+            Input::Left => "←",
+            Input::Right => "→",
+            Input::Rotate => "↻",
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn apply(shape: Shape, input: Input) -> Option<Shape> {
+    let mut next = shape;
+    match input {
+        Input::Left if can_shift_left(&next) => next.move_left(),
+        Input::Right if can_shift_right(&next) => next.move_right(),
+        Input::Rotate => {
+            next = shape.kicked_rotation(in_bounds, |shape, dir| match dir {
+                Direction::Left => apply(*shape, Input::Left),
+                Direction::Right => apply(*shape, Input::Right),
+                Direction::Down => None,
+            })?;
+        }
+        _ => return None,
+    }
+    match in_bounds(&next) {
+        true => Some(next),
+        false => None,
+    }
+}
+
+fn can_shift_left(shape: &Shape) -> bool {
+    let step = 2 * shape.scale();
+    shape.tl_coords().col >= step
+        && shape
+            .coords()
+            .iter()
+            .all(|coord| coord.col >= ARENA_TL.col + step)
+}
+
+fn can_shift_right(shape: &Shape) -> bool {
+    let step = 2 * shape.scale();
+    shape
+        .coords()
+        .iter()
+        .all(|coord| coord.col + 2 + step <= ARENA_TL.col + ARENA_DIMS.col)
+}
+
+fn in_bounds(shape: &Shape) -> bool {
+    shape
+        .coords()
+        .iter()
+        .all(|coord| coord.col >= ARENA_TL.col && coord.col + 2 <= ARENA_TL.col + ARENA_DIMS.col)
+}
+
+// whether the shape covers the same columns as the target, with the same outline
+fn is_above(shape: &Shape, target: &[Coord]) -> bool {
+    let coords = shape.coords();
+    let top = coords.iter().map(|coord| coord.row).min().unwrap();
+    let target_top = match target.iter().map(|coord| coord.row).min() {
+        Some(row) => row,
+        None => return false,
+    };
+    coords.len() == target.len()
+        && coords
+            .iter()
+            .all(|coord| target.contains(&Coord::new(coord.row - top + target_top, coord.col)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{Display, ShapeType};
+
+    fn spawn(shape_type: ShapeType) -> Shape {
+        let mut shape = Shape::from_type(shape_type);
+        shape.change_display(Display::Drop, true);
+        shape.center();
+        shape
+    }
+
+    fn coords(cells: &[(usize, usize)]) -> Vec<Coord> {
+        cells
+            .iter()
+            .map(|&(row, col)| Coord::new(row, col))
+            .collect()
+    }
+
+    #[test]
+    fn reaches_every_column_and_rotation() {
+        assert_eq!(placements(spawn(ShapeType::O)).len(), 9);
+        assert_eq!(placements(spawn(ShapeType::I)).len(), 7 + 10 + 7 + 10);
+        assert_eq!(placements(spawn(ShapeType::T)).len(), 8 + 9 + 8 + 9);
+    }
+
+    #[test]
+    fn spawn_position_only_needs_a_drop() {
+        let shape = spawn(ShapeType::T);
+        assert_eq!(finesse_path(shape, &shape.coords()), Some(Vec::new()));
+        assert_eq!(format_inputs(&[]), "drop");
+    }
+
+    #[test]
+    fn takes_the_fewest_inputs() {
+        let left_wall = coords(&[(20, 4), (20, 6), (21, 4), (21, 6)]);
+        assert_eq!(
+            finesse_path(spawn(ShapeType::O), &left_wall),
+            Some(vec![Input::Left; 5])
+        );
+        let upside_down = coords(&[(20, 10), (20, 12), (20, 14), (21, 12)]);
+        assert_eq!(
+            finesse_path(spawn(ShapeType::T), &upside_down),
+            Some(vec![Input::Rotate, Input::Rotate])
+        );
+        let vertical = coords(&[(18, 4), (19, 4), (20, 4), (21, 4)]);
+        assert_eq!(
+            finesse_path(spawn(ShapeType::I), &vertical).map(|inputs| inputs.len()),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn unreachable_targets_have_no_path() {
+        let three_blocks = coords(&[(21, 4), (21, 6), (21, 8)]);
+        assert_eq!(finesse_path(spawn(ShapeType::I), &three_blocks), None);
+    }
+
+    #[test]
+    fn rotating_against_a_wall_kicks_off_it() {
+        for mirrored in [false, true] {
+            let mut shape = spawn(ShapeType::I);
+            shape.set_mirrored(mirrored);
+            let mut shape = apply(shape, Input::Rotate).unwrap();
+            for _ in 0..4 {
+                shape = apply(shape, Input::Right).unwrap();
+            }
+            assert_eq!(
+                shape.coords(),
+                coords(&[(0, 22), (1, 22), (2, 22), (3, 22)])
+            );
+            let kicked = apply(shape, Input::Rotate).unwrap();
+            let mut cols = kicked
+                .coords()
+                .iter()
+                .map(|coord| coord.col)
+                .collect::<Vec<_>>();
+            cols.sort_unstable();
+            assert_eq!(cols, vec![16, 18, 20, 22]);
+        }
+    }
+}
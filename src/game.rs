@@ -1,5 +1,8 @@
-use ncurses::{attrset, getch, mvprintw, nodelay, stdscr, wrefresh, COLOR_PAIR};
+use ncurses::{
+    attrset, getch, mvprintw, nodelay, refresh, stdscr, wrefresh, A_REVERSE, COLOR_PAIR, ERR,
+};
 use ncurses::{KEY_DOWN, KEY_LEFT, KEY_RIGHT, KEY_UP};
+use std::collections::VecDeque;
 use std::{thread, time};
 const SPACE_CHAR: i32 = ' ' as i32;
 const E_CHAR: i32 = 'e' as i32;
@@ -7,135 +10,1080 @@ const R_CHAR: i32 = 'r' as i32;
 const N_CHAR: i32 = 'n' as i32;
 const Y_CHAR: i32 = 'y' as i32;
 const D_CHAR: i32 = 'd' as i32;
+const U_CHAR: i32 = 'u' as i32;
+const G_CHAR: i32 = 'g' as i32;
+const C_CHAR: i32 = 'c' as i32;
 
-use crate::primitives::{get_dims, get_tl, Coord, Direction, Display, Symbol};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::daily::{record, DailyResult};
+use crate::finesse::{finesse_path, format_inputs, placements, Input};
+use crate::gravity::{
+    gravity, table_gravity, GravityCurve, FRAME_DURATION, GRAVITY_UNIT, MAX_GRAVITY,
+};
+use crate::mode::{master_grade, Mode, GM_CHECKPOINTS, MASTER_MAX_LEVEL};
+use crate::opener::Opener;
+use crate::primitives::{
+    from_symbol, get_dims, get_tl, in_arena, shape_to_char, to_shape_type, Block, Clear, Coord,
+    Direction, Display, Event, Item, LineGravity, Progression, Randomizer, Scoring, ShapeType,
+    StackVisibility, Symbol, TSpin, ARENA_DIMS, ARENA_TL, BLOCK_HORIZ_MULT, GARBAGE_COLOR, ITEMS,
+};
+use crate::puzzle::{Objective, Puzzle, Setup};
 use crate::screen::Screen;
-use crate::shape::Shape;
+use crate::shape::{shuffle, shuffled_bag, Shape};
+use crate::stats::{format_time, Stats, SPLIT_LINES};
+
+pub const LOCK_DELAY: u32 = 30; // frames
+
+// one in this many pieces carries an item
+const ITEM_CHANCE: u32 = 8;
 
-const SLEEP_DURATION: u64 = 10; // milliseconds
+// entry delay and lock delay in frames, starting from each master section level
+const MASTER_DELAYS: [(u32, u32, u32); 6] = [
+    (0, 25, 30),
+    (500, 20, 27),
+    (600, 16, 24),
+    (700, 12, 21),
+    (800, 8, 18),
+    (900, 6, 15),
+];
+const SCORE_CAP: u64 = 999_999;
+const MIN_RISE_INTERVAL: u32 = 60; // frames
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Outcome {
+    GameOver,
+    Finished,
+}
+
+// everything needed to go back to just before a shape spawned
+#[derive(Clone, Debug)]
+struct Snapshot {
+    screen: Screen,
+    next_shape: Shape,
+    queue: VecDeque<ShapeType>,
+    score: u64,
+    lines: u32,
+    level: u32,
+    goal_lines: u32,
+    combo: Option<u32>,
+    back_to_back: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub mode: Mode,
+    pub start_level: u32,
+    pub is_easy: bool,
+    pub scoring: Scoring,
+    pub progression: Progression,
+    pub gravity: GravityCurve,
+    pub popup_time: u64,
+    pub score_cap: bool,
+    pub stack: StackVisibility,
+    pub big: bool,
+    pub mirror: bool,
+    pub puzzles: Vec<Puzzle>,
+    pub opener: Option<Opener>,
+    pub setup: Option<Setup>,
+    // fixes the piece sequence and garbage, otherwise they are random
+    pub seed: Option<u64>,
+    // the date of the daily challenge being played
+    pub daily: Option<String>,
+    pub randomizer: Randomizer,
+    pub preview: bool,
+    // frames
+    pub lock_delay: u32,
+    // overrides the gravity curve when not empty
+    pub gravity_table: Vec<(u32, u32)>,
+    // rows of garbage to start with, and how messy they are
+    pub garbage: (u32, u32),
+    pub line_gravity: LineGravity,
+    pub items: bool,
+}
 
 pub struct Game {
+    mode: Mode,
+    scale: usize,
+    mirror: bool,
     is_easy: bool,
-    framerate: u32,
+    scoring: Scoring,
+    score_cap: bool,
+    progression: Progression,
+    gravity_curve: GravityCurve,
+    gravity_acc: u32,
+    popup_time: time::Duration,
     rem_drop_height: usize,
     screen: Screen,
     curr_shape: Shape,
     next_shape: Shape,
     level: u32,
     start_level: u32,
-    score: u32,
+    score: u64,
     lines: u32,
+    goal_lines: u32,
+    push_down: u32,
+    last_rotated: bool,
+    combo: Option<u32>,
+    back_to_back: bool,
+    master_combo: u64,
+    is_gm: bool,
+    entry_delay: u32,
+    events: Vec<Event>,
+    stats: Stats,
+    garbage_hole: usize,
+    rise_interval: u32,
+    rise_frames: u32,
+    risen_rows: u32,
+    // pieces that are dealt before falling back to random ones
+    queue: VecDeque<ShapeType>,
+    // the current shape as it spawned, and the inputs used to move it since
+    spawn: Shape,
+    piece_inputs: u32,
+    // position of the next shape within its bag
+    bag_pos: usize,
+    // pieces placed since the current puzzle or training attempt started
+    attempt_pieces: u32,
+    attempts: u32,
+    successes: u32,
+    puzzles: Vec<Puzzle>,
+    puzzle_idx: usize,
+    opener: Option<Opener>,
+    // the board each perfect clear attempt starts from
+    setup: Vec<Vec<Symbol>>,
+    history: Vec<Snapshot>,
+    gravity_off: bool,
+    // the block being edited in the sandbox, relative to the arena
+    cursor: Option<Coord>,
+    // where each piece of the opener has to be placed
+    targets: Vec<Shape>,
+    puzzle_lines: u32,
+    last_clear: Option<Clear>,
+    // separate streams, so garbage and items never change the pieces dealt for a seed
+    rng: ChaCha8Rng,
+    garbage_rng: ChaCha8Rng,
+    item_rng: ChaCha8Rng,
+    daily: Option<String>,
+    randomizer: Randomizer,
+    preview: bool,
+    base_lock_delay: u32,
+    gravity_table: Vec<(u32, u32)>,
+    garbage: (u32, u32),
+    line_gravity: LineGravity,
+    items: bool,
 }
 
 impl Game {
-    pub fn new(screen: Screen, start_level: u32, is_easy: bool) -> Self {
+    pub fn new(mut screen: Screen, settings: Settings) -> Self {
+        // master mode counts its sections up from zero
+        let start_level = match settings.mode {
+            Mode::Master => 0,
+            _ => settings.start_level,
+        };
+        screen.update_stat_display(start_level as u64, Display::Level);
+        let mirror = settings.mirror;
+        let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let stream = |stream| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(stream);
+            rng
+        };
+        let mut rng = stream(0);
+        let mut garbage_rng = stream(1);
+        let curr_shape = Shape::new(&mut rng);
+        let next_shape = Shape::new(&mut rng);
+        let garbage_hole = (garbage_rng.gen::<u32>() % (ARENA_DIMS.col / 2) as u32) as usize;
         Self {
-            is_easy,
-            framerate: 24 - start_level,
+            mode: settings.mode,
+            scale: if settings.big { 2 } else { 1 },
+            mirror,
+            is_easy: settings.is_easy,
+            // ultra is always a guideline score attack
+            scoring: match settings.mode {
+                Mode::Ultra { .. } => Scoring::Guideline,
+                Mode::Master => Scoring::Master,
+                _ => settings.scoring,
+            },
+            score_cap: settings.score_cap,
+            progression: match settings.mode {
+                Mode::Master => Progression::Sections,
+                _ => settings.progression,
+            },
+            gravity_curve: match settings.mode {
+                Mode::Master => GravityCurve::Master,
+                _ => settings.gravity,
+            },
+            gravity_acc: 0,
+            popup_time: time::Duration::from_millis(settings.popup_time),
             rem_drop_height: 0,
             screen,
-            curr_shape: Shape::new(),
-            next_shape: Shape::new(),
+            curr_shape,
+            next_shape,
             level: start_level,
             start_level,
             score: 0,
             lines: 0,
+            goal_lines: 0,
+            push_down: 0,
+            last_rotated: false,
+            combo: None,
+            back_to_back: false,
+            master_combo: 1,
+            is_gm: true,
+            entry_delay: 0,
+            events: Vec::new(),
+            stats: Stats::new(),
+            garbage_hole,
+            rise_interval: match settings.mode {
+                Mode::Survival { interval } => interval,
+                _ => 0,
+            },
+            rise_frames: 0,
+            risen_rows: 0,
+            queue: VecDeque::new(),
+            spawn: curr_shape,
+            piece_inputs: 0,
+            bag_pos: 0,
+            attempt_pieces: 0,
+            attempts: 0,
+            successes: 0,
+            puzzles: settings.puzzles,
+            puzzle_idx: 0,
+            targets: settings
+                .opener
+                .as_ref()
+                .map_or(Vec::new(), |opener| opener.targets(mirror).unwrap()),
+            opener: settings.opener,
+            setup: settings.setup.map_or(Vec::new(), |setup| {
+                let mut board = setup.board();
+                if mirror {
+                    board.iter_mut().for_each(|row| row.reverse());
+                }
+                board
+            }),
+            history: Vec::new(),
+            gravity_off: false,
+            cursor: None,
+            puzzle_lines: 0,
+            last_clear: None,
+            rng,
+            garbage_rng,
+            item_rng: stream(2),
+            daily: settings.daily,
+            randomizer: settings.randomizer,
+            preview: settings.preview,
+            base_lock_delay: settings.lock_delay,
+            gravity_table: settings.gravity_table,
+            garbage: settings.garbage,
+            line_gravity: settings.line_gravity,
+            items: settings.items,
         }
     }
 
     pub fn run(&mut self) -> bool {
+        let (rows, messiness) = match self.mode {
+            Mode::Dig { rows, messiness } => (rows, messiness),
+            _ => self.garbage,
+        };
+        if rows > 0 {
+            let holes = self.gen_garbage(rows, messiness);
+            self.screen.add_garbage(&holes);
+        }
+        if self.mode == Mode::Puzzle {
+            self.load_puzzle();
+        }
+        if self.mode == Mode::Opener {
+            self.load_opener();
+        }
+        if let Mode::PerfectClear { bag_offset, .. } = self.mode {
+            self.screen.set_board(&self.setup);
+            self.queue = shuffled_bag(&mut self.rng)
+                .into_iter()
+                .skip(bag_offset)
+                .collect();
+            self.bag_pos = bag_offset;
+            self.deal_bags();
+            self.next_shape = Shape::from_type(self.queue.pop_front().unwrap());
+        } else if self.randomizer == Randomizer::Bag {
+            self.deal_bags();
+            self.next_shape = Shape::from_type(self.queue.pop_front().unwrap());
+        }
+
         let mut new_shape = true;
-        let mut count = 0;
-        let mut stand_still = 0;
+        let mut lock_frames = 0;
+        let mut outcome = Outcome::GameOver;
 
         loop {
             if new_shape {
-                self.gen_shape();
+                if self.entry_delay > 0 {
+                    self.entry_delay -= 1;
+                    thread::sleep(time::Duration::from_millis(FRAME_DURATION));
+                    continue;
+                }
+                if !self.gen_shape() {
+                    break;
+                }
                 new_shape = false;
+                lock_frames = 0;
             }
-            if count % self.framerate() == 0 {
-                if self.rem_drop_height > 0 {
-                    if !self.drop_shape() {
-                        break;
-                    }
-                } else if self.curr_shape.display() == Display::Drop {
-                    self.curr_shape.change_display(Display::Arena, false);
+
+            self.gravity_acc += self.gravity();
+            let mut rows = self.gravity_acc / GRAVITY_UNIT;
+            self.gravity_acc %= GRAVITY_UNIT;
+            // without gravity a new shape still has to enter the arena
+            if self.gravity_off && self.curr_shape.display() == Display::Drop {
+                rows = self.rem_drop_height as u32 + 1;
+            }
+            if !self.fall(rows) {
+                break;
+            }
+
+            if let Mode::Survival { .. } = self.mode {
+                if !self.rise() {
+                    break;
                 }
-                if !self.move_shape(Direction::Down) {
-                    stand_still += 1;
-                    if !self.is_easy && stand_still > 1 || stand_still > 2 {
-                        self.curr_shape.kill();
-                    }
-                } else {
-                    stand_still = 0;
+            }
+
+            if self.is_grounded() && self.cursor.is_none() {
+                lock_frames += 1;
+                if lock_frames >= self.lock_delay() {
+                    self.curr_shape.kill();
                 }
+            } else {
+                lock_frames = 0;
             }
 
-            thread::sleep(time::Duration::from_millis(SLEEP_DURATION));
-            match getch() {
-                KEY_UP => self.rotate(),
-                KEY_DOWN => {
-                    self.move_shape(Direction::Down);
+            thread::sleep(time::Duration::from_millis(FRAME_DURATION));
+            let key = match self.cursor {
+                Some(_) => self.edit(getch()),
+                None => getch(),
+            };
+            if let KEY_UP | KEY_DOWN | KEY_LEFT | KEY_RIGHT | SPACE_CHAR = key {
+                self.stats.add_key();
+            }
+            if let KEY_UP | KEY_LEFT | KEY_RIGHT = key {
+                self.piece_inputs += 1;
+            }
+            match key {
+                KEY_UP => {
+                    self.push_down = 0;
+                    self.rotate();
                 }
+                KEY_DOWN => self.soft_drop(),
                 KEY_LEFT => {
+                    self.push_down = 0;
                     self.move_shape(Direction::Left);
                 }
                 KEY_RIGHT => {
+                    self.push_down = 0;
                     self.move_shape(Direction::Right);
                 }
                 SPACE_CHAR => self.ground(),
                 E_CHAR => self.is_easy ^= true,
                 R_CHAR => break,
+                key if self.mode == Mode::Sandbox => new_shape |= self.sandbox_input(key),
                 _ => (),
             }
 
+            self.screen
+                .update_text_display(&self.stats.panel(), Display::Stats);
+            self.screen
+                .update_text_display(&[self.status()], Display::Status);
             self.screen.draw();
-            if self.is_easy {
-                self.draw(self.ground_dist());
+            // the landing spot is always shown over the target outline, marking any mismatch
+            if let Some(target) = self.targets.get(self.attempt_pieces as usize) {
+                self.draw(target, 0, "[]");
+                self.draw_landing(target);
+            } else if self.is_easy {
+                self.draw(&self.curr_shape, self.ground_dist(), "██");
             }
-            self.draw(0);
+            self.draw(&self.curr_shape, 0, "██");
+            self.draw_item(&self.curr_shape);
+            if let Some(cursor) = self.cursor {
+                mvprintw(
+                    (ARENA_TL.row + cursor.row) as i32,
+                    (ARENA_TL.col + cursor.col * 2) as i32,
+                    "<>",
+                );
+            }
+            self.screen.draw_popups();
             self.screen.top();
 
             if self.curr_shape.is_dead() {
+                let fault = self.finesse_fault();
+                if fault.is_some() {
+                    self.stats.add_fault();
+                }
                 self.screen.set_shape(self.curr_shape);
+                self.stats.add_piece(self.curr_shape.shape_type());
                 self.points();
-                thread::sleep(time::Duration::from_millis(SLEEP_DURATION));
+                self.handle_events();
+                thread::sleep(time::Duration::from_millis(FRAME_DURATION));
+                self.entry_delay = self.master_delays().0;
                 new_shape = true;
+
+                if self.mode == Mode::Puzzle {
+                    match self.puzzle_result() {
+                        Some(true) if self.puzzle_idx + 1 == self.puzzles.len() => {
+                            self.screen.disp_banner("SOLVED");
+                            outcome = Outcome::Finished;
+                            break;
+                        }
+                        Some(true) => {
+                            self.screen.disp_banner("SOLVED");
+                            self.puzzle_idx += 1;
+                            self.load_puzzle();
+                        }
+                        Some(false) => {
+                            self.screen.disp_banner("FAILED");
+                            self.load_puzzle();
+                        }
+                        None => (),
+                    }
+                }
+                if let Mode::PerfectClear { pieces, .. } = self.mode {
+                    self.end_attempt(pieces);
+                }
+                if self.mode == Mode::Opener {
+                    self.check_placement();
+                }
+                if self.mode == Mode::Finesse {
+                    self.check_finesse(fault);
+                }
+            }
+
+            if self.is_finished() {
+                outcome = Outcome::Finished;
+                break;
             }
+        }
+
+        self.stats.stop();
+        self.game_over(outcome)
+    }
+
+    // the hole column for each new garbage row, moving with a chance of `messiness` percent
+    fn gen_garbage(&mut self, rows: u32, messiness: u32) -> Vec<usize> {
+        let cols = ARENA_DIMS.col / 2;
+        (0..rows)
+            .map(|_| {
+                if self.garbage_rng.gen::<u32>() % 100 < messiness {
+                    let shift = self.garbage_rng.gen::<u32>() % (cols as u32 - 1);
+                    self.garbage_hole = (self.garbage_hole + 1 + shift as usize) % cols;
+                }
+                self.garbage_hole
+            })
+            .collect()
+    }
+
+    // pushes a garbage row up from the bottom whenever the interval runs out, shortening the
+    // interval each time. returns false if this tops out
+    fn rise(&mut self) -> bool {
+        self.rise_frames += 1;
+        if self.rise_frames < self.rise_interval {
+            return true;
+        }
+        self.rise_frames = 0;
+        self.rise_interval = (self.rise_interval * 9 / 10).max(MIN_RISE_INTERVAL);
+        self.risen_rows += 1;
 
-            count += 1;
+        let holes = self.gen_garbage(1, 100);
+        if !self.screen.add_garbage(&holes) {
+            return false;
+        }
+        if self.curr_shape.display() == Display::Arena && !self.fits(self.curr_shape) {
+            self.curr_shape.move_up();
+            return self.fits(self.curr_shape);
         }
+        true
+    }
 
-        self.game_over()
+    // sets up the board and pieces of the current puzzle, also used to retry it
+    fn load_puzzle(&mut self) {
+        let puzzle = &self.puzzles[self.puzzle_idx];
+        let mut board = puzzle.board();
+        if self.mirror {
+            board.iter_mut().for_each(|row| row.reverse());
+        }
+        self.screen.set_board(&board);
+        self.queue = puzzle.shapes().into_iter().collect();
+        self.next_shape = Shape::from_type(self.queue.pop_front().unwrap());
+        self.attempt_pieces = 0;
+        self.puzzle_lines = 0;
+        self.last_clear = None;
+        self.combo = None;
+        self.back_to_back = false;
     }
 
-    fn framerate(&self) -> u32 {
-        self.framerate + match self.is_easy {
-            true => self.framerate / 2,
-            false => 0
+    // Some(true) once the objective is met, Some(false) once it no longer can be
+    fn puzzle_result(&self) -> Option<bool> {
+        let puzzle = &self.puzzles[self.puzzle_idx];
+        let solved = match (puzzle.objective, self.last_clear) {
+            (Objective::Lines(lines), _) => self.puzzle_lines >= lines,
+            (Objective::TspinTriple, Some(clear)) => clear.tspin == TSpin::Full && clear.lines == 3,
+            (Objective::PerfectClear(pieces), Some(clear)) => {
+                clear.perfect && self.attempt_pieces <= pieces
+            }
+            (_, None) => false,
+        };
+        if solved {
+            Some(true)
+        } else if self.attempt_pieces >= puzzle.piece_limit() {
+            Some(false)
+        } else {
+            None
         }
     }
 
-    fn rotate(&mut self) {
-        let mut test = self.curr_shape;
-        test.rotate_right();
-        let occupies_empty_space = test.coords().iter().all(|coord| {
-            self.screen.is_space(coord.row, coord.col)
-                && self.screen.is_space(coord.row, coord.col + 1)
-        });
-        if occupies_empty_space {
-        } else if self.space_available(test, Direction::Left) {
-            self.move_shape(Direction::Left);
-        } else if self.space_available(test, Direction::Right) {
-            self.move_shape(Direction::Right);
+    // clears the board and deals the opener's pieces again
+    fn load_opener(&mut self) {
+        self.screen.set_board(&[]);
+        self.queue = self.opener.as_ref().unwrap().shapes().into_iter().collect();
+        self.next_shape = Shape::from_type(self.queue.pop_front().unwrap());
+        self.attempt_pieces = 0;
+        self.combo = None;
+        self.back_to_back = false;
+    }
+
+    // restarts the opener after a piece misses its target or once every piece is placed
+    fn check_placement(&mut self) {
+        let target = self.targets[self.attempt_pieces as usize - 1];
+        let coords = target.coords();
+        let is_match = self
+            .curr_shape
+            .coords()
+            .iter()
+            .all(|coord| coords.contains(coord));
+        if !is_match {
+            // shows the piece that missed against the outline of where it should have gone
+            for _ in 0..3 {
+                self.draw(&target, 0, "[]");
+                refresh();
+                thread::sleep(time::Duration::from_millis(150));
+                self.screen.draw();
+                refresh();
+                thread::sleep(time::Duration::from_millis(75));
+            }
+            self.screen.disp_banner("FAILED");
+        } else if self.attempt_pieces as usize == self.targets.len() {
+            self.successes += 1;
+            self.screen.disp_banner("COMPLETE");
         } else {
             return;
         }
-        self.curr_shape.rotate_right();
+        self.attempts += 1;
+        self.load_opener();
+    }
+
+    // the fewest inputs for the placement that was aimed for, if the player used more than that.
+    // placements that can't be hard dropped into, like tucks and spins, are never faults
+    fn finesse_fault(&self) -> Option<Vec<Input>> {
+        let target = match self.mode {
+            Mode::Finesse => self.targets[0].coords(),
+            _ => self.curr_shape.coords(),
+        };
+        let is_droppable = target.iter().all(|coord| {
+            (get_tl(Display::Arena).row..coord.row).all(|row| self.screen.is_space(row, coord.col))
+        });
+        if !is_droppable {
+            return None;
+        }
+        let best = finesse_path(self.spawn, &target)?;
+        match self.piece_inputs as usize > best.len() {
+            true => Some(best),
+            false => None,
+        }
+    }
+
+    // any placement reachable from the spawn, resting on the floor
+    fn random_target(&mut self) -> Shape {
+        let options = placements(self.spawn);
+        let (mut target, _) = options[(self.rng.gen::<u32>() % options.len() as u32) as usize];
+        target.change_display(Display::Arena, false);
+        let floor = get_tl(Display::Arena).row + get_dims(Display::Arena).row;
+        while target.bounding_box()[1].row < floor {
+            target.move_down();
+        }
+        target
+    }
+
+    // shows the fastest way to the target after a miss or a finesse fault, then clears the board
+    fn check_finesse(&mut self, fault: Option<Vec<Input>>) {
+        let coords = self.targets[0].coords();
+        let is_match = self
+            .curr_shape
+            .coords()
+            .iter()
+            .all(|coord| coords.contains(coord));
+        let best = match (is_match, fault) {
+            (true, None) => {
+                self.successes += 1;
+                None
+            }
+            (true, Some(best)) => Some(best),
+            (false, _) => finesse_path(self.spawn, &coords),
+        };
+        if let Some(best) = best {
+            let miss = match is_match {
+                true => "FAULT",
+                false => "MISSED",
+            };
+            self.screen.add_popups(
+                &[miss.to_string(), format!("best {}", format_inputs(&best))],
+                self.popup_time,
+            );
+        }
+        self.attempts += 1;
+        self.attempt_pieces = 0;
+        self.screen.set_board(&[]);
+    }
+
+    // sandbox controls, returns true if the current shape has to be replaced
+    fn sandbox_input(&mut self, key: i32) -> bool {
+        match key {
+            U_CHAR => {
+                self.undo();
+                true
+            }
+            G_CHAR => {
+                self.gravity_off ^= true;
+                false
+            }
+            C_CHAR => {
+                self.cursor = Some(Coord::new(ARENA_DIMS.row / 2, ARENA_DIMS.col / 4));
+                false
+            }
+            key => {
+                let chr = std::char::from_u32(key as u32).unwrap_or(' ');
+                let shape_type = match to_shape_type(chr) {
+                    Some(shape_type) => shape_type,
+                    None => return false,
+                };
+                // lowercase picks the current shape, uppercase the next one
+                if chr.is_ascii_uppercase() {
+                    self.next_shape = Shape::from_type(shape_type);
+                    self.next_shape.set_mirrored(self.mirror);
+                    self.screen.add_next(&self.next_shape);
+                    return false;
+                }
+                self.history.pop();
+                self.queue.push_front(self.next_shape.shape_type());
+                self.next_shape = Shape::from_type(shape_type);
+                true
+            }
+        }
+    }
+
+    // goes back to the board, shapes and score from before the previous shape spawned.
+    // the latest snapshot belongs to the current shape, so that one is skipped
+    fn undo(&mut self) {
+        if self.history.len() > 1 {
+            self.history.pop();
+        }
+        let snapshot = match self.history.pop() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        self.screen = snapshot.screen;
+        self.next_shape = snapshot.next_shape;
+        self.queue = snapshot.queue;
+        self.score = snapshot.score;
+        self.lines = snapshot.lines;
+        self.level = snapshot.level;
+        self.goal_lines = snapshot.goal_lines;
+        self.combo = snapshot.combo;
+        self.back_to_back = snapshot.back_to_back;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            screen: self.screen.clone(),
+            next_shape: self.next_shape,
+            queue: self.queue.clone(),
+            score: self.score,
+            lines: self.lines,
+            level: self.level,
+            goal_lines: self.goal_lines,
+            combo: self.combo,
+            back_to_back: self.back_to_back,
+        }
+    }
+
+    // moves the editing cursor or paints and erases the block under it,
+    // keys that aren't used for editing are passed through
+    fn edit(&mut self, key: i32) -> i32 {
+        let mut cursor = self.cursor.unwrap();
+        match key {
+            KEY_UP => cursor.row = cursor.row.saturating_sub(1),
+            KEY_DOWN => cursor.row = (cursor.row + 1).min(ARENA_DIMS.row - 1),
+            KEY_LEFT => cursor.col = cursor.col.saturating_sub(1),
+            KEY_RIGHT => cursor.col = (cursor.col + 1).min(ARENA_DIMS.col / 2 - 1),
+            SPACE_CHAR => {
+                let coord = Coord::new(ARENA_TL.row + cursor.row, ARENA_TL.col + cursor.col * 2);
+                if !self.curr_shape.coords().contains(&coord) {
+                    let block = match self.screen.is_space(coord.row, coord.col) {
+                        true => Symbol::DeadBlock(Block::new(GARBAGE_COLOR)),
+                        false => Symbol::Space,
+                    };
+                    self.screen.set_block(coord, block);
+                }
+            }
+            C_CHAR => {
+                self.cursor = None;
+                return ERR;
+            }
+            key => return key,
+        }
+        self.cursor = Some(cursor);
+        ERR
+    }
+
+    // keeps at least a full bag of upcoming pieces in the queue
+    fn deal_bags(&mut self) {
+        while self.queue.len() < 7 {
+            self.queue.extend(shuffled_bag(&mut self.rng));
+        }
+    }
+
+    // an attempt ends with a perfect clear or once all of its pieces are placed,
+    // after which the board is reset to the setup for the next one
+    fn end_attempt(&mut self, pieces: u32) {
+        let perfect = self.last_clear.is_some_and(|clear| clear.perfect);
+        if !perfect && self.attempt_pieces < pieces {
+            return;
+        }
+        self.attempts += 1;
+        if perfect {
+            self.successes += 1;
+        } else {
+            self.screen.disp_banner("FAILED");
+        }
+        self.screen.set_board(&self.setup);
+        self.attempt_pieces = 0;
+        self.last_clear = None;
+        self.combo = None;
+        self.back_to_back = false;
+    }
+
+    // saves the result of a daily challenge, returning the line shown for it
+    fn record_daily(&self, date: &str, outcome: Outcome) -> String {
+        let result = DailyResult {
+            mode: self.mode.name().to_string(),
+            finished: outcome == Outcome::Finished,
+            score: self.score,
+            lines: self.lines,
+            time: format_time(self.stats.elapsed()),
+        };
+        match record(date, result) {
+            Ok(attempts) => format!("daily {} #{}", date, attempts),
+            Err(e) => format!("not saved: {}", e),
+        }
+    }
+
+    fn success_rate(&self) -> u32 {
+        (self.successes * 100)
+            .checked_div(self.attempts)
+            .unwrap_or(0)
+    }
+
+    // the upcoming pieces starting with the next one, with a bar where a new bag begins
+    fn bag_preview(&self) -> String {
+        let mut preview = String::new();
+        let upcoming =
+            std::iter::once(self.next_shape.shape_type()).chain(self.queue.iter().copied());
+        for (idx, shape_type) in upcoming.take(7).enumerate() {
+            if idx > 0 && (self.bag_pos + idx).is_multiple_of(7) {
+                preview.push('|');
+            }
+            preview.push(shape_to_char(shape_type));
+        }
+        preview
+    }
+
+    // whether another piece follows the current one, puzzles only have a fixed sequence
+    fn has_next(&self) -> bool {
+        match self.mode {
+            Mode::Puzzle => {
+                self.attempt_pieces + 1 < self.puzzles[self.puzzle_idx].shapes().len() as u32
+            }
+            Mode::Opener => self.attempt_pieces as usize + 1 < self.targets.len(),
+            Mode::Finesse | Mode::Sandbox => true,
+            _ => self.preview,
+        }
+    }
+
+    fn fits(&self, shape: Shape) -> bool {
+        shape.coords().iter().all(|coord| {
+            self.screen.is_space(coord.row, coord.col)
+                && self.screen.is_space(coord.row, coord.col + 1)
+        })
+    }
+
+    fn is_finished(&self) -> bool {
+        match self.mode {
+            Mode::Endless => false,
+            // the final level has to be completed, not just reached
+            Mode::Marathon { level, lines } => self.level > level || self.lines >= lines,
+            Mode::Sprint { lines } => self.lines >= lines,
+            Mode::Ultra { duration } => self.stats.elapsed() >= duration,
+            Mode::Dig { .. } => self.screen.garbage_rows() == 0,
+            Mode::Survival { .. } => false,
+            Mode::Master => self.level >= MASTER_MAX_LEVEL,
+            Mode::Puzzle
+            | Mode::PerfectClear { .. }
+            | Mode::Opener
+            | Mode::Finesse
+            | Mode::Sandbox => false,
+        }
+    }
+
+    // the mode specific progress shown underneath the arena
+    fn status(&self) -> String {
+        match self.mode {
+            Mode::Endless => self.mode.name().to_string(),
+            Mode::Marathon { level, lines } => format!(
+                "{}   level {}/{}   lines {}/{}",
+                self.mode.name(),
+                self.level.min(level),
+                level,
+                self.lines.min(lines),
+                lines
+            ),
+            Mode::Sprint { lines } => format!(
+                "{} {}   left {}   time {}",
+                self.mode.name(),
+                lines,
+                lines.saturating_sub(self.lines),
+                format_time(self.stats.elapsed())
+            ),
+            Mode::Ultra { duration } => format!(
+                "{}   time left {}",
+                self.mode.name(),
+                format_time(
+                    duration
+                        .checked_sub(self.stats.elapsed())
+                        .unwrap_or_default()
+                )
+            ),
+            Mode::Dig { .. } => format!(
+                "{}   garbage left {}   time {}",
+                self.mode.name(),
+                self.screen.garbage_rows(),
+                format_time(self.stats.elapsed())
+            ),
+            Mode::Master => format!(
+                "{}   section {}   grade {}   time {}",
+                self.mode.name(),
+                self.level / 100 + 1,
+                master_grade(self.score, false),
+                format_time(self.stats.elapsed())
+            ),
+            Mode::Puzzle => format!(
+                "{} {}/{}   {}   pieces left {}",
+                self.mode.name(),
+                self.puzzle_idx + 1,
+                self.puzzles.len(),
+                self.puzzles[self.puzzle_idx].objective.describe(),
+                self.puzzles[self.puzzle_idx]
+                    .piece_limit()
+                    .saturating_sub(self.attempt_pieces)
+            ),
+            Mode::PerfectClear { pieces, .. } => format!(
+                "{}   {}/{}   queue {}   rate {}/{} {}%",
+                self.mode.name(),
+                self.attempt_pieces,
+                pieces,
+                self.bag_preview(),
+                self.successes,
+                self.attempts,
+                self.success_rate()
+            ),
+            Mode::Sandbox => match self.cursor {
+                Some(_) => format!(
+                    "{}   editing   space paint/erase   c done",
+                    self.mode.name()
+                ),
+                None => format!(
+                    "{}  g gravity {}  u undo  c edit  ijlostz/IJLOSTZ",
+                    self.mode.name(),
+                    if self.gravity_off { "off" } else { "on" }
+                ),
+            },
+            Mode::Finesse => format!(
+                "{}   faults {}   clean {}/{} {}%",
+                self.mode.name(),
+                self.stats.faults(),
+                self.successes,
+                self.attempts,
+                self.success_rate()
+            ),
+            Mode::Opener => format!(
+                "{} {}   piece {}/{}   done {}/{} {}%",
+                self.mode.name(),
+                self.opener.as_ref().unwrap().name,
+                self.attempt_pieces + 1,
+                self.targets.len(),
+                self.successes,
+                self.attempts,
+                self.success_rate()
+            ),
+            Mode::Survival { .. } => format!(
+                "{}   next rise {:.1}s   risen {}",
+                self.mode.name(),
+                (self.rise_interval - self.rise_frames) as f64 * FRAME_DURATION as f64 / 1000.0,
+                self.risen_rows
+            ),
+        }
+    }
+
+    fn results(&self) -> Vec<String> {
+        match self.mode {
+            Mode::Endless
+            | Mode::Survival { .. }
+            | Mode::PerfectClear { .. }
+            | Mode::Opener
+            | Mode::Finesse
+            | Mode::Sandbox => vec!["Game over!".to_string()],
+            Mode::Marathon { .. } => vec![
+                "Congratulations!".to_string(),
+                "Marathon complete".to_string(),
+                format!("score {}", self.score),
+                format!("time {}", format_time(self.stats.elapsed())),
+            ],
+            Mode::Sprint { .. } => {
+                let mut results = vec![
+                    "Sprint complete!".to_string(),
+                    format!("time {}", format_time(self.stats.elapsed())),
+                    format!("pps {:.2}", self.stats.pps()),
+                ];
+                for (idx, split) in self.stats.splits().iter().enumerate() {
+                    results.push(format!(
+                        "{:>3} lines {}",
+                        (idx as u32 + 1) * SPLIT_LINES,
+                        format_time(*split)
+                    ));
+                }
+                results
+            }
+            Mode::Ultra { .. } => vec![
+                "Time's up!".to_string(),
+                format!("score {}", self.score),
+                format!("lines {}", self.lines),
+                format!("pps {:.2}", self.stats.pps()),
+            ],
+            Mode::Master => vec![
+                "Master complete!".to_string(),
+                format!("grade {}", master_grade(self.score, self.is_gm)),
+                format!("score {}", self.score),
+                format!("time {}", format_time(self.stats.elapsed())),
+            ],
+            Mode::Puzzle => vec![
+                "Pack complete!".to_string(),
+                format!("{} puzzles solved", self.puzzles.len()),
+                format!("time {}", format_time(self.stats.elapsed())),
+            ],
+            Mode::Dig { rows, .. } => vec![
+                "Dig complete!".to_string(),
+                format!("{} rows dug", rows),
+                format!("time {}", format_time(self.stats.elapsed())),
+                format!("pps {:.2}", self.stats.pps()),
+            ],
+        }
+    }
+
+    // the gravity of the current level, before easy mode slows it down
+    fn base_gravity(&self) -> u32 {
+        if self.gravity_off || self.cursor.is_some() {
+            return 0;
+        }
+        match self.gravity_table.is_empty() {
+            true => gravity(self.gravity_curve, self.level),
+            false => table_gravity(&self.gravity_table, self.level),
+        }
+    }
+
+    fn gravity(&self) -> u32 {
+        let gravity = self.base_gravity();
+        match self.is_easy {
+            true => gravity * 2 / 3,
+            false => gravity,
+        }
+    }
+
+    fn lock_delay(&self) -> u32 {
+        let lock_delay = match self.mode {
+            Mode::Master => self.master_delays().1,
+            _ => self.base_lock_delay,
+        };
+        match self.is_easy {
+            true => lock_delay * 3 / 2,
+            false => lock_delay,
+        }
+    }
+
+    // the entry delay and lock delay for the current section, only master mode has an entry delay
+    fn master_delays(&self) -> (u32, u32) {
+        match self.mode {
+            Mode::Master => {
+                let (_, entry, lock) = MASTER_DELAYS
+                    .iter()
+                    .rev()
+                    .find(|(start, _, _)| self.level >= *start)
+                    .unwrap();
+                (*entry, *lock)
+            }
+            _ => (0, LOCK_DELAY),
+        }
+    }
+
+    // moves the shape down by up to `rows`, stopping at the first collision.
+    // returns false if the shape is blocked while still entering the arena
+    fn fall(&mut self, rows: u32) -> bool {
+        for _ in 0..rows {
+            if self.rem_drop_height > 0 {
+                if !self.drop_shape() {
+                    return false;
+                }
+                continue;
+            }
+            if self.curr_shape.display() == Display::Drop {
+                self.curr_shape.change_display(Display::Arena, false);
+            }
+            if !self.move_shape(Direction::Down) {
+                break;
+            }
+        }
+        true
+    }
+
+    fn is_grounded(&self) -> bool {
+        self.curr_shape.display() == Display::Arena
+            && !self.space_available(self.curr_shape, Direction::Down)
+    }
+
+    fn rotate(&mut self) {
+        let rotated = self.curr_shape.kicked_rotation(
+            |shape| self.fits(*shape),
+            |shape, dir| {
+                let mut shifted = *shape;
+                match dir {
+                    _ if !self.space_available(shifted, dir) => return None,
+                    Direction::Left => shifted.move_left(),
+                    Direction::Right => shifted.move_right(),
+                    Direction::Down => shifted.move_down(),
+                }
+                Some(shifted)
+            },
+        );
+        if let Some(rotated) = rotated {
+            self.curr_shape = rotated;
+            self.last_rotated = true;
+        }
     }
 
     fn move_shape(&mut self, dir: Direction) -> bool {
-        match dir {
+        let moved = match dir {
             Direction::Left => {
                 if self.space_available(self.curr_shape, dir) {
                     self.curr_shape.move_left();
@@ -160,34 +1108,54 @@ impl Game {
                     false
                 }
             }
+        };
+        if moved {
+            self.last_rotated = false;
         }
+        moved
     }
 
+    // checks every cell a shape would pass over, scaled shapes move more than one cell per step
     fn space_available(&self, shape: Shape, dir: Direction) -> bool {
+        let step = shape.scale();
         shape.coords().iter().all(|coord| match dir {
             Direction::Left => {
-                self.screen.is_space(coord.row, coord.col - 2)
-                    && self.screen.is_space(coord.row, coord.col - 1)
-            }
-            Direction::Right => {
-                self.screen.is_space(coord.row, coord.col + 1)
-                    && self.screen.is_space(coord.row, coord.col + 2)
-            }
-            Direction::Down => {
-                self.screen.is_space(coord.row + 1, coord.col)
-                    && self.screen.is_space(coord.row + 1, coord.col + 1)
+                ((coord.col - 2 * step)..coord.col).all(|col| self.screen.is_space(coord.row, col))
             }
+            Direction::Right => ((coord.col + 2)..(coord.col + 2 + 2 * step))
+                .all(|col| self.screen.is_space(coord.row, col)),
+            Direction::Down => ((coord.row + 1)..=(coord.row + step)).all(|row| {
+                self.screen.is_space(row, coord.col) && self.screen.is_space(row, coord.col + 1)
+            }),
         })
     }
 
+    fn soft_drop(&mut self) {
+        if self.move_shape(Direction::Down) {
+            self.add_drop_points(1, false);
+        }
+    }
+
     fn ground(&mut self) {
         if self.curr_shape.display() == Display::Drop {
             return;
         }
+        self.add_drop_points(self.ground_dist() as u32, true);
         while self.move_shape(Direction::Down) {}
         self.curr_shape.kill();
     }
 
+    fn add_drop_points(&mut self, cells: u32, is_hard_drop: bool) {
+        match self.scoring {
+            // NES-style push-down points are only paid out once the piece locks
+            Scoring::Nes | Scoring::Master => self.push_down += cells,
+            Scoring::Guideline => {
+                self.add_score(if is_hard_drop { cells * 2 } else { cells } as u64);
+                self.screen.update_stat_display(self.score, Display::Score);
+            }
+        }
+    }
+
     fn ground_dist(&self) -> usize {
         let mut down = 0;
         let mut test = self.curr_shape;
@@ -202,14 +1170,17 @@ impl Game {
 
     fn drop_shape(&mut self) -> bool {
         // assert!(self.curr_shape.display() == Display::Arena);
+        let step = self.curr_shape.scale();
         let would_occupy_empty_space = self.curr_shape.coords().iter().any(|coord| {
-            match (
-                self.screen.get_cell(coord.row + 1, coord.col),
-                self.screen.get_cell(coord.row + 1, coord.col + 1),
-            ) {
-                (Symbol::DeadBlock(_), _) | (_, Symbol::DeadBlock(_)) => true,
-                _ => false,
-            }
+            ((coord.row + 1)..=(coord.row + step)).any(|row| {
+                matches!(
+                    (
+                        self.screen.get_cell(row, coord.col),
+                        self.screen.get_cell(row, coord.col + 1),
+                    ),
+                    (Symbol::DeadBlock(_), _) | (_, Symbol::DeadBlock(_))
+                )
+            })
         });
         if would_occupy_empty_space {
             return false;
@@ -219,101 +1190,503 @@ impl Game {
         true
     }
 
-    fn draw(&self, down: usize) {
-        attrset(COLOR_PAIR(self.curr_shape.color_num()));
-        for coord in self.curr_shape.descent_coords(down).iter() {
-            mvprintw(coord.row as i32, coord.col as i32, "██");
+    fn draw(&self, shape: &Shape, down: usize, glyph: &str) {
+        attrset(COLOR_PAIR(shape.color_num()));
+        for coord in shape.descent_coords(down).iter() {
+            mvprintw(coord.row as i32, coord.col as i32, glyph);
         }
         attrset(COLOR_PAIR(1));
     }
 
-    fn gen_shape(&mut self) {
+    // the ghost of the current shape, where blocks that miss the target are reversed
+    fn draw_landing(&self, target: &Shape) {
+        let coords = target.coords();
+        let color = COLOR_PAIR(self.curr_shape.color_num());
+        for coord in self.curr_shape.descent_coords(self.ground_dist()) {
+            let (attrs, glyph) = match coords.contains(&coord) {
+                true => (color, "██"),
+                false => (color | A_REVERSE(), "><"),
+            };
+            attrset(attrs);
+            mvprintw(coord.row as i32, coord.col as i32, glyph);
+        }
+        attrset(COLOR_PAIR(1));
+    }
+
+    fn draw_item(&self, shape: &Shape) {
+        for (coord, symbol) in shape.blocks() {
+            if let Symbol::LiveBlock(block) = symbol {
+                if block.item.is_some() {
+                    attrset(COLOR_PAIR(block.color) | A_REVERSE());
+                    let glyph = from_symbol(symbol).to_string().repeat(2);
+                    mvprintw(coord.row as i32, coord.col as i32, &glyph);
+                }
+            }
+        }
+        attrset(COLOR_PAIR(1));
+    }
+
+    // returns false if the new shape tops out
+    fn gen_shape(&mut self) -> bool {
+        // timed modes run out and master's time limits count even if nothing is pressed
+        if let Mode::Ultra { .. } | Mode::Survival { .. } | Mode::Master = self.mode {
+            self.stats.start();
+        }
+        if self.mode == Mode::Sandbox {
+            self.history.push(self.snapshot());
+        }
         self.curr_shape = self.next_shape;
-        while self.next_shape.shape_type() == self.curr_shape.shape_type() {
-            self.next_shape = Shape::new();
+        if matches!(self.mode, Mode::PerfectClear { .. }) || self.randomizer == Randomizer::Bag {
+            self.deal_bags();
+            self.bag_pos = (self.bag_pos + 1) % 7;
+        }
+        match self.queue.pop_front() {
+            Some(shape_type) => self.next_shape = Shape::from_type(shape_type),
+            None => {
+                while self.next_shape.shape_type() == self.curr_shape.shape_type() {
+                    self.next_shape = Shape::new(&mut self.rng);
+                }
+            }
+        }
+
+        if self.items && self.item_rng.gen::<u32>() % ITEM_CHANCE == 0 {
+            let item = ITEMS[(self.item_rng.gen::<u32>() % ITEMS.len() as u32) as usize];
+            let block = self.item_rng.gen::<u32>() % 4;
+            self.next_shape.set_item(block as usize, item);
         }
 
-        self.rem_drop_height = 2;
+        self.curr_shape.set_scale(self.scale);
+        self.curr_shape.set_mirrored(self.mirror);
+        self.next_shape.set_mirrored(self.mirror);
+        self.rem_drop_height = 2 / self.scale;
+        self.gravity_acc = 0;
+        self.push_down = 0;
 
         self.curr_shape.change_display(Display::Drop, true);
         self.curr_shape.center();
-        self.screen.add_next(&self.next_shape);
+        self.spawn = self.curr_shape;
+        self.piece_inputs = 0;
+        if self.mode == Mode::Finesse {
+            self.targets = vec![self.random_target()];
+        }
+        if self.has_next() {
+            self.screen.add_next(&self.next_shape);
+        } else {
+            self.screen.wipe_display(Display::Next);
+        }
         self.drop_shape();
+
+        // every new piece advances the section level, except when it is about to end a section
+        if self.progression == Progression::Sections
+            && self.level % 100 != 99
+            && self.level < MASTER_MAX_LEVEL - 1
+        {
+            self.advance_section_level(1);
+        }
+
+        // at 20G the shape appears directly on top of the stack
+        if self.base_gravity() >= MAX_GRAVITY {
+            return self.fall(get_dims(Display::Arena).row as u32);
+        }
+        true
+    }
+
+    fn add_score(&mut self, points: u64) {
+        self.score += points;
+        if self.score_cap {
+            self.score = self.score.min(SCORE_CAP);
+        }
     }
 
-    fn points_earned(&self, rows_filled: usize) -> u32 {
-        (match rows_filled {
-            0 => 0,
-            1 => 40,
-            2 => 100,
-            3 => 300,
-            4 => 1200,
-            _ => 1200,
-        }) * self.level
+    // the level before the clear and the soft drop distance only count when clearing lines
+    fn master_points(&self, rows_filled: usize) -> u64 {
+        let lines = rows_filled as u64;
+        let base = (self.level as u64 + lines).div_ceil(4) + self.push_down as u64;
+        base * lines * self.master_combo
     }
 
-    fn points(&mut self) -> bool {
+    fn points_earned(&self, clear: &Clear) -> u64 {
+        let points = match (self.scoring, clear.tspin, clear.lines) {
+            (Scoring::Master, _, rows) => return self.master_points(rows),
+            (Scoring::Nes, _, 0) => 0,
+            (Scoring::Nes, _, 1) => 40,
+            (Scoring::Nes, _, 2) => 100,
+            (Scoring::Nes, _, 3) => 300,
+            (Scoring::Nes, _, _) => 1200,
+            (Scoring::Guideline, TSpin::None, 0) => 0,
+            (Scoring::Guideline, TSpin::None, 1) => 100,
+            (Scoring::Guideline, TSpin::None, 2) => 300,
+            (Scoring::Guideline, TSpin::None, 3) => 500,
+            (Scoring::Guideline, TSpin::None, _) => 800,
+            (Scoring::Guideline, TSpin::Mini, 0) => 100,
+            (Scoring::Guideline, TSpin::Mini, 1) => 200,
+            (Scoring::Guideline, TSpin::Mini, _) => 400,
+            (Scoring::Guideline, TSpin::Full, 0) => 400,
+            (Scoring::Guideline, TSpin::Full, 1) => 800,
+            (Scoring::Guideline, TSpin::Full, 2) => 1200,
+            (Scoring::Guideline, TSpin::Full, _) => 1600,
+        };
+        let points = match (self.scoring, clear.back_to_back) {
+            (Scoring::Guideline, true) => points * 3 / 2,
+            _ => points,
+        };
+        let combo = match self.scoring {
+            Scoring::Guideline => 50 * clear.combo,
+            _ => 0,
+        };
+        (points + combo) as u64 * self.level as u64
+    }
+
+    fn perfect_clear_bonus(&self, clear: &Clear, earned: u64) -> u64 {
+        if self.scoring == Scoring::Master {
+            // a master bravo quadruples the points for the clear
+            return earned * 3;
+        }
+        (match (clear.lines, clear.back_to_back) {
+            (4, true) => 3200,
+            (1, _) => 800,
+            (2, _) => 1200,
+            (3, _) => 1800,
+            _ => 2000,
+        }) * self.level as u64
+    }
+
+    // three-corner rule: a T that was rotated into place with at least three of the
+    // corners around its center occupied, where both corners it points at make it a full spin
+    fn tspin(&self) -> TSpin {
+        if self.curr_shape.shape_type() != ShapeType::T || !self.last_rotated {
+            return TSpin::None;
+        }
+        let tl = self.curr_shape.tl_coords();
+        let scale = self.curr_shape.scale();
+        let is_occupied = |row: usize, col: usize| {
+            let coord = Coord::new(tl.row + row * scale, tl.col + col * 2 * scale);
+            !in_arena(coord.row, coord.col) || !self.screen.is_space(coord.row, coord.col)
+        };
+        let corners = [(0, 0), (0, 2), (2, 2), (2, 0)]
+            .iter()
+            .map(|(row, col)| is_occupied(*row, *col))
+            .collect::<Vec<bool>>();
+        // the two corners on the side the T points at, following its clockwise rotations
+        let front = self.curr_shape.rotations() as usize;
+        let front_corners = [corners[front], corners[(front + 1) % 4]];
+
+        match corners.iter().filter(|corner| **corner).count() {
+            0..=2 => TSpin::None,
+            _ if front_corners.iter().all(|corner| *corner) => TSpin::Full,
+            _ => TSpin::Mini,
+        }
+    }
+
+    fn full_lines(&self) -> Vec<usize> {
         let start = get_tl(Display::Arena);
         let end = start + get_dims(Display::Arena);
-        let full_lines = (start.row..end.row)
+        (start.row..end.row)
             .filter(|row| {
-                (start.col..end.col).all(|col| match self.screen.get_cell(*row, col) {
-                    Symbol::DeadBlock(_) | Symbol::LiveBlock(_) => true,
-                    _ => false,
+                (start.col..end.col).all(|col| {
+                    matches!(
+                        self.screen.get_cell(*row, col),
+                        Symbol::DeadBlock(_) | Symbol::LiveBlock(_)
+                    )
                 })
             })
-            .collect::<Vec<usize>>();
+            .collect()
+    }
+
+    fn points(&mut self) -> bool {
+        let full_lines = self.full_lines();
+
+        // in big mode a line of blocks is two rows tall
+        let mut clear = Clear {
+            lines: full_lines.len().div_ceil(self.scale),
+            tspin: self.tspin(),
+            combo: 0,
+            back_to_back: false,
+            perfect: false,
+            chain: 0,
+        };
+        if clear.lines > 0 {
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+            clear.combo = self.combo.unwrap();
+            clear.back_to_back = self.back_to_back && clear.is_difficult();
+            self.back_to_back = clear.is_difficult();
+        } else {
+            self.combo = None;
+            self.master_combo = 1;
+        }
 
-        self.lines += full_lines.len() as u32;
-        self.score += self.points_earned(full_lines.len());
+        self.stats.add_clear(clear.lines);
+        self.lines += clear.lines as u32;
+        self.puzzle_lines += clear.lines as u32;
+        self.attempt_pieces += 1;
+        if clear.lines > 0 {
+            self.master_combo += 2 * clear.lines as u64 - 2;
+        }
+        let earned = self.points_earned(&clear);
+        match self.scoring {
+            Scoring::Master => self.add_score(earned),
+            _ => self.add_score(earned + self.push_down as u64),
+        }
+        self.push_down = 0;
+
+        // items going off do not count towards a perfect clear
+        clear.perfect = clear.lines > 0 && self.screen.only_lines_left(&full_lines);
+        self.clear_lines(&full_lines);
+
+        if clear.perfect {
+            self.add_score(self.perfect_clear_bonus(&clear, earned));
+        }
+        self.last_clear = Some(clear);
+        if clear.lines > 0 || clear.tspin != TSpin::None {
+            self.emit(Event::Clear(clear));
+        }
+        let mut advanced = self.update_level(clear.lines);
+        if self.line_gravity == LineGravity::Cascade {
+            advanced |= self.chain_clears();
+        }
 
         self.screen.update_stat_display(self.score, Display::Score);
-        self.screen.update_stat_display(self.lines, Display::Lines);
+        self.screen
+            .update_stat_display(self.lines as u64, Display::Lines);
 
-        self.screen.shift_lines(&full_lines);
+        advanced
+    }
 
-        if (self.level == self.start_level && self.lines > self.start_level * 10 + 10)
-            || (self.lines >= self.level * 10)
-        {
-            self.advance_level();
-            return true;
+    // clears the full lines, setting off the items in them
+    fn clear_lines(&mut self, full_lines: &[usize]) {
+        let mut lines = full_lines.to_vec();
+        let mut items = self.screen.items(&lines);
+        // bombs can blow up lines holding more items, including other bombs
+        loop {
+            let mut blasted = lines.clone();
+            for (coord, item) in items.iter() {
+                if *item == Item::LineBomb {
+                    let rows = (coord.row.saturating_sub(self.scale)..coord.row + 2 * self.scale)
+                        .filter(|row| in_arena(*row, coord.col));
+                    blasted.extend(rows);
+                }
+            }
+            blasted.sort_unstable();
+            blasted.dedup();
+            if blasted.len() == lines.len() {
+                break;
+            }
+            lines = blasted;
+            items = self.screen.items(&lines);
         }
 
-        false
+        // cascading blocks only settle once the items have gone off
+        match self.line_gravity {
+            LineGravity::Naive => self.screen.shift_lines(&lines),
+            LineGravity::Cascade => self.screen.empty_lines(&lines),
+        }
+        for (coord, item) in items.iter() {
+            match item {
+                Item::LineBomb => (),
+                Item::ColumnClear => {
+                    // a big block spans several columns, and the item sits on its left one
+                    let width = BLOCK_HORIZ_MULT * self.scale;
+                    let left = coord.col - (coord.col - ARENA_TL.col) % width;
+                    self.screen.clear_column(left, width)
+                }
+                Item::GravityFlip => self.screen.flip_stack(),
+                Item::NextShuffle => self.shuffle_next(),
+            }
+        }
+        if !items.is_empty() {
+            let names = items
+                .iter()
+                .map(|(_, item)| item.name().to_string())
+                .collect::<Vec<String>>();
+            self.screen.add_popups(&names, self.popup_time);
+        }
+        if self.line_gravity == LineGravity::Cascade {
+            self.screen.settle();
+        }
     }
 
-    fn advance_level(&mut self) {
-        self.level += 1;
-        if self.framerate > 2 {
-            self.framerate -= 1;
+    // swaps the next piece for another one. with a bag, only the rest of the bag the next piece
+    // is in gets shuffled, which leaves it unchanged when the next piece is the last one
+    fn shuffle_next(&mut self) {
+        let next = self.next_shape.shape_type();
+        if self.queue.is_empty() {
+            while self.next_shape.shape_type() == next {
+                self.next_shape = Shape::new(&mut self.item_rng);
+            }
+        } else {
+            self.queue.push_front(next);
+            let bag_left = 7 - self.bag_pos;
+            shuffle(
+                &mut self.queue.make_contiguous()[..bag_left],
+                &mut self.item_rng,
+            );
+            self.next_shape = Shape::from_type(self.queue.pop_front().unwrap());
+        }
+        self.next_shape.set_mirrored(self.mirror);
+        if self.has_next() {
+            self.screen.add_next(&self.next_shape);
         }
     }
 
-    fn game_over(&mut self) -> bool {
-        nodelay(stdscr(), false);
-        wrefresh(stdscr());
+    // clears the lines completed by blocks falling in a cascade until none are left,
+    // each link of the chain multiplying its points. returns whether the level went up
+    fn chain_clears(&mut self) -> bool {
+        let mut advanced = false;
+        let mut chain = 0;
+        loop {
+            let full_lines = self.full_lines();
+            if full_lines.is_empty() {
+                return advanced;
+            }
+            chain += 1;
+            let mut clear = Clear {
+                lines: full_lines.len().div_ceil(self.scale),
+                tspin: TSpin::None,
+                combo: 0,
+                back_to_back: false,
+                perfect: false,
+                chain,
+            };
+            self.stats.add_clear(clear.lines);
+            self.lines += clear.lines as u32;
+            self.puzzle_lines += clear.lines as u32;
+            let earned = self.points_earned(&clear) * (chain as u64 + 1);
+            self.add_score(earned);
+
+            clear.perfect = self.screen.only_lines_left(&full_lines);
+            self.clear_lines(&full_lines);
+            if clear.perfect {
+                self.add_score(self.perfect_clear_bonus(&clear, earned));
+            }
+            self.emit(Event::Clear(clear));
+            // every link counts as a clear of its own towards the level
+            advanced |= self.update_level(clear.lines);
+        }
+    }
+
+    fn update_level(&mut self, rows_filled: usize) -> bool {
+        if self.progression == Progression::Sections {
+            if rows_filled > 0 {
+                self.advance_section_level(rows_filled as u32);
+            }
+            return false;
+        }
+
+        self.goal_lines += match (self.progression, rows_filled) {
+            // the variable goal counts awarded lines rather than cleared ones
+            (Progression::Variable, 2) => 3,
+            (Progression::Variable, 3) => 5,
+            (Progression::Variable, 4) => 8,
+            (_, rows) => rows as u32,
+        };
+
+        let mut advanced = false;
+        while self.level_goal_reached() {
+            self.advance_level();
+            advanced = true;
+        }
+        if advanced {
+            self.screen
+                .update_stat_display(self.level as u64, Display::Level);
+        }
+        advanced
+    }
 
-        for row in 8..12 {
-            for col in 4..24 {
-                mvprintw(row, col, " ");
+    fn level_goal_reached(&self) -> bool {
+        match self.progression {
+            Progression::Fixed => self.lines >= (self.level - self.start_level + 1) * 10,
+            Progression::Nes => {
+                (self.level == self.start_level && self.lines > self.start_level * 10 + 10)
+                    || (self.lines >= self.level * 10)
             }
+            Progression::Variable => self.goal_lines >= self.level * 5,
+            Progression::Sections => false,
         }
+    }
+
+    fn advance_section_level(&mut self, amount: u32) {
+        let before = self.level;
+        self.level = (self.level + amount).min(MASTER_MAX_LEVEL);
+        for (level, score, time) in GM_CHECKPOINTS.iter() {
+            if before < *level
+                && self.level >= *level
+                && (self.score < *score || self.stats.elapsed() > *time)
+            {
+                self.is_gm = false;
+            }
+        }
+        self.screen
+            .update_stat_display(self.level as u64, Display::Level);
+    }
 
-        mvprintw(11, 3, "┣");
-        mvprintw(8, 3, "┣");
+    fn emit(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    fn handle_events(&mut self) {
+        for event in std::mem::take(&mut self.events) {
+            match event {
+                Event::Clear(clear) => {
+                    if clear.perfect {
+                        self.screen.disp_banner("ALL CLEAR");
+                    }
+                    self.screen.add_popups(&clear.popups(), self.popup_time);
+                }
+            }
+        }
+    }
 
-        for row in 4..25 {
-            mvprintw(8, row, "━");
-            mvprintw(11, row, "━");
+    fn advance_level(&mut self) {
+        if self.progression == Progression::Variable {
+            self.goal_lines -= self.level * 5;
         }
+        self.level += 1;
+    }
 
-        mvprintw(11, 24, "┫");
-        mvprintw(8, 24, "┫");
+    fn game_over(&mut self, outcome: Outcome) -> bool {
+        nodelay(stdscr(), false);
+        wrefresh(stdscr());
 
-        self.screen.top();
+        self.screen.reveal();
+        self.screen.draw();
 
-        mvprintw(9, 9, "Game over!");
-        mvprintw(10, 6, "Try again? (y/n)");
+        let mut lines = match (outcome, self.mode) {
+            (Outcome::GameOver, Mode::Survival { .. }) => vec![
+                "Game over!".to_string(),
+                format!("survived {}", format_time(self.stats.elapsed())),
+                format!("{} rows risen", self.risen_rows),
+            ],
+            (Outcome::GameOver, Mode::Master) => vec![
+                "Game over!".to_string(),
+                format!("grade {}", master_grade(self.score, false)),
+                format!("level {}", self.level),
+            ],
+            (Outcome::GameOver, Mode::PerfectClear { .. }) => vec![
+                "Training over".to_string(),
+                format!("{} attempts", self.attempts),
+                format!("{} perfect clears", self.successes),
+                format!("success rate {}%", self.success_rate()),
+            ],
+            (Outcome::GameOver, Mode::Finesse) => vec![
+                "Training over".to_string(),
+                format!("{} placements", self.attempts),
+                format!("{} clean", self.successes),
+                format!("{} faults", self.stats.faults()),
+            ],
+            (Outcome::GameOver, Mode::Opener) => vec![
+                "Practice over".to_string(),
+                format!("{} attempts", self.attempts),
+                format!("{} completed", self.successes),
+                format!("success rate {}%", self.success_rate()),
+            ],
+            (Outcome::GameOver, _) => vec!["Game over!".to_string()],
+            (Outcome::Finished, _) => self.results(),
+        };
+        if let Some(date) = self.daily.clone() {
+            lines.push(self.record_daily(&date, outcome));
+        }
+        lines.push("Try again? (y/n)".to_string());
+        self.screen.disp_dialog(&lines);
 
         loop {
             match getch() {
@@ -0,0 +1,86 @@
+// gravity is measured in 1/65536ths of a row per frame
+pub const GRAVITY_UNIT: u32 = 65536;
+pub const MAX_GRAVITY: u32 = 20 * GRAVITY_UNIT;
+pub const FRAME_DURATION: u64 = 16; // milliseconds, roughly 60 frames per second
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GravityCurve {
+    Nes,
+    Guideline,
+    TwentyG,
+    Master,
+}
+
+pub fn to_gravity_curve(name: &str) -> GravityCurve {
+    match name {
+        "guideline" => GravityCurve::Guideline,
+        "20g" => GravityCurve::TwentyG,
+        _ => GravityCurve::Nes,
+    }
+}
+
+// internal gravity in 1/256ths of a row per frame, starting from each section level
+const MASTER_GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+// frames per row for levels 0 through 29, anything above uses the last entry
+const NES_FRAMES_PER_ROW: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+
+pub fn gravity(curve: GravityCurve, level: u32) -> u32 {
+    match curve {
+        GravityCurve::Nes => {
+            GRAVITY_UNIT / NES_FRAMES_PER_ROW[(level as usize).min(NES_FRAMES_PER_ROW.len() - 1)]
+        }
+        GravityCurve::Guideline => {
+            // past level 20 the guideline formula is well beyond 20G (and eventually undefined)
+            let level = level.clamp(1, 20) as f64;
+            let seconds_per_row = (0.8 - (level - 1.0) * 0.007).powf(level - 1.0);
+            ((GRAVITY_UNIT as f64 / (seconds_per_row * 60.0)) as u32).min(MAX_GRAVITY)
+        }
+        GravityCurve::TwentyG => MAX_GRAVITY,
+        GravityCurve::Master => table_gravity(&MASTER_GRAVITY, level),
+    }
+}
+
+// gravity from pairs of a starting level and internal gravity, sorted by level from 0
+pub fn table_gravity(table: &[(u32, u32)], level: u32) -> u32 {
+    let (_, internal) = table
+        .iter()
+        .rev()
+        .find(|(start, _)| level >= *start)
+        .unwrap();
+    internal * (GRAVITY_UNIT / 256)
+}
@@ -1,16 +1,59 @@
 use ncurses::{
     constants::{stdscr, LcCategory},
     curs_set, endwin, init_pair, initscr, keypad, nodelay, noecho, setlocale, start_color,
-    use_default_colors, CURSOR_VISIBILITY,
+    use_default_colors, COLORS, CURSOR_VISIBILITY,
 };
 
 use clap::{App, Arg};
+use std::process;
+mod custom;
+mod daily;
+mod finesse;
 mod game;
+mod gravity;
+mod mode;
+mod opener;
 mod primitives;
+mod puzzle;
 mod screen;
 mod shape;
+mod stats;
 
-use primitives::{num_to_shape, shape_color};
+use custom::load_custom_mode;
+use daily::{daily_mode, date_seed, today};
+use game::{Settings, LOCK_DELAY};
+use gravity::to_gravity_curve;
+use mode::{to_mode, Mode, ModeGoals};
+use opener::load_opener;
+use primitives::{
+    garbage_color, num_to_shape, shape_color, to_line_gravity, to_progression, to_scoring,
+    to_stack_visibility, Randomizer, GARBAGE_COLOR,
+};
+use puzzle::{load_puzzles, load_setup};
+
+// the arguments, with their flags, that would change the daily challenge
+const DAILY_FIXED: [(&str, &str); 12] = [
+    ("start_level", "start-level"),
+    ("scoring", "scoring"),
+    ("progression", "progression"),
+    ("gravity", "gravity"),
+    ("line_gravity", "line-gravity"),
+    ("score_cap", "score-cap"),
+    ("stack", "stack"),
+    ("fade_time", "fade-time"),
+    ("big", "big"),
+    ("mirror", "mirror"),
+    ("items", "items"),
+    ("difficulty", "easy"),
+];
+
+fn is_positive(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(num) if num > 0 => Ok(()),
+        Ok(_) => Err("value must be positive".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
 fn main() {
     setlocale(LcCategory::ctype, "");
@@ -19,6 +62,124 @@ fn main() {
         .version("1.0")
         .author("Phil Nadon <phil@nadon.io>")
         .about("Tetris game implemented in Rust. Original: https://github.com/k-vernooy/tetris")
+        .arg(
+            Arg::with_name("mode")
+                .short("m")
+                .long("mode")
+                .possible_values(&[
+                    "endless", "marathon", "sprint", "ultra", "dig", "survival", "master",
+                    "puzzle", "pc", "opener", "finesse", "sandbox", "daily", "custom",
+                ])
+                .default_value("endless")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("marathon_level")
+                .long("marathon-level")
+                .validator(is_positive)
+                .default_value("15")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("marathon_lines")
+                .long("marathon-lines")
+                .validator(is_positive)
+                .default_value("150")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sprint_lines")
+                .long("sprint-lines")
+                .possible_values(&["20", "40", "100"])
+                .default_value("40")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ultra_time")
+                .long("ultra-time")
+                .validator(is_positive)
+                .default_value("120")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("garbage_rows")
+                .long("garbage-rows")
+                .validator(|rows| match rows.parse::<u32>() {
+                    Ok(1..=17) => Ok(()),
+                    Ok(_) => Err("garbage rows must be between 1 and 17".to_string()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .default_value("10")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("messiness")
+                .long("messiness")
+                .validator(|percent| match percent.parse::<u32>() {
+                    Ok(0..=100) => Ok(()),
+                    Ok(_) => Err("messiness is a percentage".to_string()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .default_value("50")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rise_time")
+                .long("rise-time")
+                .validator(is_positive)
+                .default_value("8")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pc_pieces")
+                .long("pc-pieces")
+                .validator(is_positive)
+                .default_value("10")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pc_number")
+                .long("pc-number")
+                .possible_values(&["1", "2", "3", "4", "5", "6", "7"])
+                .default_value("1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pc_setups")
+                .long("pc-setups")
+                .requires("pc_setup")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pc_setup")
+                .long("pc-setup")
+                .requires("pc_setups")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("puzzles")
+                .long("puzzles")
+                .required_if("mode", "puzzle")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("custom")
+                .long("custom")
+                .required_if("mode", "custom")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("openers")
+                .long("openers")
+                .required_if("mode", "opener")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("opener")
+                .long("opener")
+                .required_if("mode", "opener")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("start_level")
                 .short("s")
@@ -35,6 +196,63 @@ fn main() {
                 })
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("scoring")
+                .long("scoring")
+                .possible_values(&["nes", "guideline"])
+                .default_value("nes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("progression")
+                .long("progression")
+                .possible_values(&["fixed", "nes", "variable"])
+                .default_value("nes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("gravity")
+                .long("gravity")
+                .possible_values(&["nes", "guideline", "20g"])
+                .default_value("nes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("line_gravity")
+                .long("line-gravity")
+                .possible_values(&["naive", "cascade"])
+                .default_value("naive")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("popup_time")
+                .long("popup-time")
+                .validator(|time| time.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .default_value("1500")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("score_cap")
+                .long("score-cap")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("stack")
+                .long("stack")
+                .possible_values(&["normal", "invisible", "fade"])
+                .default_value("normal")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fade_time")
+                .long("fade-time")
+                .validator(is_positive)
+                .default_value("5")
+                .takes_value(true),
+        )
+        .arg(Arg::with_name("big").long("big").takes_value(false))
+        .arg(Arg::with_name("mirror").long("mirror").takes_value(false))
+        .arg(Arg::with_name("items").long("items").takes_value(false))
         .arg(
             Arg::with_name("difficulty")
                 .short("e")
@@ -44,11 +262,129 @@ fn main() {
         .get_matches();
 
     let start_level: u32 = matches
-        .value_of("start-level")
+        .value_of("start_level")
         .unwrap_or("8")
         .parse()
         .unwrap();
-    let is_easy = matches.is_present("difficulty");
+    let puzzles = match matches.value_of("puzzles") {
+        Some(path) => load_puzzles(path).unwrap_or_else(|e| {
+            eprintln!("could not load puzzles from {}", e);
+            process::exit(1);
+        }),
+        None => Vec::new(),
+    };
+    let opener = match (matches.value_of("openers"), matches.value_of("opener")) {
+        (Some(path), Some(name)) => Some(load_opener(path, name).unwrap_or_else(|e| {
+            eprintln!("could not load opener from {}", e);
+            process::exit(1);
+        })),
+        _ => None,
+    };
+    let setup = match (matches.value_of("mode"), matches.value_of("pc_setups")) {
+        (Some("pc"), Some(path)) => Some(
+            load_setup(path, matches.value_of("pc_setup").unwrap()).unwrap_or_else(|e| {
+                eprintln!("could not load setup from {}", e);
+                process::exit(1);
+            }),
+        ),
+        _ => None,
+    };
+    // the daily challenge derives both its mode and its pieces from the date
+    let daily = match matches.value_of("mode") {
+        Some("daily") => Some(today().unwrap_or_else(|e| {
+            eprintln!("could not read the date, {}", e);
+            process::exit(1);
+        })),
+        _ => None,
+    };
+    let seed = daily.as_deref().map(date_seed);
+    let custom = match (matches.value_of("mode"), matches.value_of("custom")) {
+        (Some("custom"), Some(name)) => Some(load_custom_mode(name).unwrap_or_else(|e| {
+            eprintln!("could not load mode from {}", e);
+            process::exit(1);
+        })),
+        _ => None,
+    };
+    let mut settings = Settings {
+        mode: match seed {
+            Some(seed) => daily_mode(seed),
+            None => to_mode(
+                matches.value_of("mode").unwrap(),
+                ModeGoals {
+                    marathon_level: matches.value_of("marathon_level").unwrap().parse().unwrap(),
+                    marathon_lines: matches.value_of("marathon_lines").unwrap().parse().unwrap(),
+                    sprint_lines: matches.value_of("sprint_lines").unwrap().parse().unwrap(),
+                    ultra_secs: matches.value_of("ultra_time").unwrap().parse().unwrap(),
+                    garbage_rows: matches.value_of("garbage_rows").unwrap().parse().unwrap(),
+                    messiness: matches.value_of("messiness").unwrap().parse().unwrap(),
+                    rise_secs: matches.value_of("rise_time").unwrap().parse().unwrap(),
+                    pc_pieces: setup
+                        .as_ref()
+                        .and_then(|setup| setup.pieces)
+                        .unwrap_or_else(|| matches.value_of("pc_pieces").unwrap().parse().unwrap()),
+                    pc_number: matches.value_of("pc_number").unwrap().parse().unwrap(),
+                },
+            ),
+        },
+        start_level,
+        is_easy: matches.is_present("difficulty"),
+        scoring: to_scoring(matches.value_of("scoring").unwrap()),
+        progression: to_progression(matches.value_of("progression").unwrap()),
+        gravity: to_gravity_curve(matches.value_of("gravity").unwrap()),
+        popup_time: matches.value_of("popup_time").unwrap().parse().unwrap(),
+        score_cap: matches.is_present("score_cap"),
+        big: matches.is_present("big"),
+        mirror: matches.is_present("mirror"),
+        puzzles,
+        opener,
+        setup,
+        seed,
+        daily,
+        randomizer: Randomizer::Random,
+        preview: true,
+        lock_delay: LOCK_DELAY,
+        gravity_table: Vec::new(),
+        garbage: (0, 0),
+        line_gravity: to_line_gravity(matches.value_of("line_gravity").unwrap()),
+        items: matches.is_present("items"),
+        stack: to_stack_visibility(
+            matches.value_of("stack").unwrap(),
+            matches.value_of("fade_time").unwrap().parse().unwrap(),
+        ),
+    };
+    if let Some(custom) = custom {
+        custom.apply(&mut settings);
+    }
+    // garbage, puzzle and setup boards and placement targets are all laid out in normal sized blocks
+    let fixed_blocks = matches!(
+        settings.mode,
+        Mode::Dig { .. } | Mode::Survival { .. } | Mode::Puzzle | Mode::Opener | Mode::Finesse
+    ) || settings.setup.is_some();
+    if settings.big && (fixed_blocks || settings.garbage.0 > 0) {
+        eprintln!(
+            "--big can not be used in {} mode or with starting garbage",
+            settings.mode.name().to_lowercase()
+        );
+        process::exit(1);
+    }
+    // everyone plays the same daily challenge, so its results can be compared
+    if settings.daily.is_some() {
+        let changed = DAILY_FIXED
+            .iter()
+            .find(|(name, _)| matches.occurrences_of(name) > 0);
+        if let Some((_, flag)) = changed {
+            eprintln!("--{} can not be used in daily mode", flag);
+            process::exit(1);
+        }
+    }
+    // puzzles and openers deal a fixed sequence that items would reorder
+    if settings.items && matches!(settings.mode, Mode::Puzzle | Mode::Opener) {
+        eprintln!(
+            "--items can not be used in {} mode",
+            settings.mode.name().to_lowercase()
+        );
+        process::exit(1);
+    }
 
     loop {
         initscr();
@@ -61,8 +397,9 @@ fn main() {
         for color in 1..8 {
             init_pair(color, shape_color(num_to_shape(color)), -1);
         }
-        let screen: screen::Screen = screen::Screen::new();
-        let mut game_instance = game::Game::new(screen, start_level, is_easy);
+        init_pair(GARBAGE_COLOR, garbage_color(COLORS()), -1);
+        let screen: screen::Screen = screen::Screen::new(settings.stack);
+        let mut game_instance = game::Game::new(screen, settings.clone());
         if !game_instance.run() {
             break;
         }
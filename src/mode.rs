@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use crate::gravity::FRAME_DURATION;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    Endless,
+    Marathon { level: u32, lines: u32 },
+    Sprint { lines: u32 },
+    Ultra { duration: Duration },
+    Dig { rows: u32, messiness: u32 },
+    // the number of frames before the first garbage row rises
+    Survival { interval: u32 },
+    Master,
+    Puzzle,
+    // attempts of `pieces` pieces, dealt from a 7-bag that starts `bag_offset` pieces in
+    PerfectClear { pieces: u32, bag_offset: usize },
+    Opener,
+    Finesse,
+    Sandbox,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Endless => "ENDLESS",
+            Mode::Marathon { .. } => "MARATHON",
+            Mode::Sprint { .. } => "SPRINT",
+            Mode::Ultra { .. } => "ULTRA",
+            Mode::Dig { .. } => "DIG",
+            Mode::Survival { .. } => "SURVIVAL",
+            Mode::Master => "MASTER",
+            Mode::Puzzle => "PUZZLE",
+            Mode::PerfectClear { .. } => "PERFECT CLEAR",
+            Mode::Opener => "OPENER",
+            Mode::Finesse => "FINESSE",
+            Mode::Sandbox => "SANDBOX",
+        }
+    }
+}
+
+pub struct ModeGoals {
+    pub marathon_level: u32,
+    pub marathon_lines: u32,
+    pub sprint_lines: u32,
+    pub ultra_secs: u64,
+    pub garbage_rows: u32,
+    pub messiness: u32,
+    pub rise_secs: u32,
+    pub pc_pieces: u32,
+    pub pc_number: u32,
+}
+
+pub fn to_mode(name: &str, goals: ModeGoals) -> Mode {
+    match name {
+        "marathon" => Mode::Marathon {
+            level: goals.marathon_level,
+            lines: goals.marathon_lines,
+        },
+        "sprint" => Mode::Sprint {
+            lines: goals.sprint_lines,
+        },
+        "ultra" => Mode::Ultra {
+            duration: Duration::from_secs(goals.ultra_secs),
+        },
+        "dig" => Mode::Dig {
+            rows: goals.garbage_rows,
+            messiness: goals.messiness,
+        },
+        "survival" => Mode::Survival {
+            interval: goals.rise_secs * 1000 / FRAME_DURATION as u32,
+        },
+        "master" => Mode::Master,
+        "puzzle" => Mode::Puzzle,
+        "opener" => Mode::Opener,
+        "finesse" => Mode::Finesse,
+        "sandbox" => Mode::Sandbox,
+        // every perfect clear uses up ten pieces, so each one in a row starts further into the bag
+        "pc" => Mode::PerfectClear {
+            pieces: goals.pc_pieces,
+            bag_offset: ((goals.pc_number - 1) * 10 % 7) as usize,
+        },
+        _ => Mode::Endless,
+    }
+}
+
+// the sprint lengths offered on the command line
+pub const SPRINT_LINES: [u32; 3] = [20, 40, 100];
+
+pub const MASTER_MAX_LEVEL: u32 = 999;
+
+// the score required for a grade, from 9 up to S9
+const MASTER_GRADES: [(u64, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100_000, "S8"),
+    (120_000, "S9"),
+];
+
+// the level, minimum score and maximum time to stay in the running for the grand master grade
+pub const GM_CHECKPOINTS: [(u32, u64, Duration); 3] = [
+    (300, 12000, Duration::from_secs(4 * 60 + 15)),
+    (500, 40000, Duration::from_secs(7 * 60 + 30)),
+    (MASTER_MAX_LEVEL, 126_000, Duration::from_secs(13 * 60 + 30)),
+];
+
+pub fn master_grade(score: u64, is_gm: bool) -> &'static str {
+    if is_gm {
+        return "GM";
+    }
+    MASTER_GRADES
+        .iter()
+        .rev()
+        .find(|(min_score, _)| score >= *min_score)
+        .map(|(_, grade)| *grade)
+        .unwrap()
+}
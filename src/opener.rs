@@ -0,0 +1,178 @@
+use serde::Deserialize;
+use std::fs;
+
+use crate::primitives::{to_shape_type, Coord, Display, ShapeType, ARENA_DIMS, ARENA_TL};
+use crate::shape::Shape;
+
+// marks which step of the opener a block is placed in, so the same piece can appear more than once
+const STEP_CHARS: &str = "123456789abcdefghijklmnopqrstuvwxyz";
+
+// an opener is drawn top to bottom with one character per block, where '.' or ' ' is empty
+// and every other block holds the step that places it. `pieces` lists the piece for each step
+#[derive(Clone, Debug, Deserialize)]
+pub struct Opener {
+    pub name: String,
+    pub pieces: String,
+    pub board: String,
+}
+
+#[derive(Deserialize)]
+struct OpenerBook {
+    opener: Vec<Opener>,
+}
+
+impl Opener {
+    pub fn shapes(&self) -> Vec<ShapeType> {
+        self.pieces.chars().filter_map(to_shape_type).collect()
+    }
+
+    // where each piece has to end up, in the order they are dealt
+    pub fn targets(&self, mirrored: bool) -> Result<Vec<Shape>, String> {
+        let rows = self
+            .board
+            .lines()
+            .map(|row| row.trim_end())
+            .skip_while(|row| row.is_empty())
+            .collect::<Vec<&str>>();
+        if rows.len() >= ARENA_DIMS.row
+            || rows
+                .iter()
+                .any(|row| row.chars().count() > ARENA_DIMS.col / 2)
+        {
+            return Err(format!("opener \"{}\" does not fit the arena", self.name));
+        }
+
+        let bottom = ARENA_TL.row + ARENA_DIMS.row;
+        let mut steps = vec![Vec::new(); self.shapes().len()];
+        for (row_idx, row) in rows.iter().enumerate() {
+            for (col_idx, chr) in row.chars().enumerate() {
+                if chr == '.' || chr == ' ' {
+                    continue;
+                }
+                let col = match mirrored {
+                    true => ARENA_DIMS.col / 2 - 1 - col_idx,
+                    false => col_idx,
+                };
+                let coord = Coord::new(bottom - rows.len() + row_idx, ARENA_TL.col + col * 2);
+                match STEP_CHARS.find(chr) {
+                    Some(step) if step < steps.len() => steps[step].push(coord),
+                    _ => {
+                        return Err(format!(
+                            "opener \"{}\" has an unknown step {}",
+                            self.name, chr
+                        ))
+                    }
+                }
+            }
+        }
+
+        self.shapes()
+            .into_iter()
+            .zip(steps)
+            .enumerate()
+            .map(|(idx, (shape_type, cells))| {
+                placement(shape_type, mirrored, &cells).ok_or_else(|| {
+                    format!(
+                        "step {} of opener \"{}\" is not a {:?} piece",
+                        idx + 1,
+                        self.name,
+                        shape_type
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+// the shape of the given type covering exactly the given blocks, if there is one
+fn placement(shape_type: ShapeType, mirrored: bool, cells: &[Coord]) -> Option<Shape> {
+    let top = cells.iter().map(|cell| cell.row).min()?;
+    let left = cells.iter().map(|cell| cell.col).min()?;
+    for rotations in 0..4 {
+        let mut shape = Shape::from_type(shape_type);
+        shape.set_mirrored(mirrored);
+        shape.change_display(Display::Arena, true);
+        for _ in 0..rotations {
+            shape.rotate_right();
+        }
+        let [tl, _] = shape.bounding_box();
+        for _ in tl.row..top {
+            shape.move_down();
+        }
+        for _ in top..tl.row {
+            shape.move_up();
+        }
+        for _ in (tl.col..left).step_by(2) {
+            shape.move_right();
+        }
+        for _ in (left..tl.col).step_by(2) {
+            shape.move_left();
+        }
+        let coords = shape.coords();
+        if coords.len() == cells.len() && coords.iter().all(|coord| cells.contains(coord)) {
+            return Some(shape);
+        }
+    }
+    None
+}
+
+pub fn load_opener(path: &str, name: &str) -> Result<Opener, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let book: OpenerBook = toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    let opener = book
+        .opener
+        .iter()
+        .find(|opener| opener.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let names = book
+                .opener
+                .iter()
+                .map(|opener| opener.name.as_str())
+                .collect::<Vec<&str>>();
+            format!(
+                "{}: no opener named {}, try one of: {}",
+                path,
+                name,
+                names.join(", ")
+            )
+        })?;
+    if opener.shapes().is_empty() {
+        return Err(format!("opener \"{}\" has no pieces", opener.name));
+    }
+    if let Some(chr) = opener
+        .pieces
+        .chars()
+        .find(|chr| !chr.is_whitespace() && to_shape_type(*chr).is_none())
+    {
+        return Err(format!(
+            "opener \"{}\" has unknown piece {}",
+            opener.name, chr
+        ));
+    }
+    // the game asks for the mirrored targets when --mirror is on
+    opener.targets(false)?;
+    opener.targets(true)?;
+    Ok(opener.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPENERS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openers/openers.toml");
+
+    #[test]
+    fn loads_every_bundled_opener() {
+        let book: OpenerBook = toml::from_str(&fs::read_to_string(OPENERS).unwrap()).unwrap();
+        for opener in book.opener.iter() {
+            assert!(
+                load_opener(OPENERS, &opener.name).is_ok(),
+                "{}",
+                opener.name
+            );
+        }
+        for name in ["TKI", "DT cannon", "PCO"].iter() {
+            assert!(load_opener(OPENERS, name).is_ok(), "{}", name);
+        }
+    }
+}
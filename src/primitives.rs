@@ -2,37 +2,45 @@ use ncurses::{
     COLOR_BLUE, COLOR_CYAN, COLOR_GREEN, COLOR_MAGENTA, COLOR_RED, COLOR_WHITE, COLOR_YELLOW,
 };
 use std::ops;
+use std::time::Duration;
 
-pub static SCREEN_STR: &str = "                                       
-   ┏━━pnadon/tetris-rs━━┓              
-   ┃                    ┃              
-   ┃                    ┃   ┏━━next━━━┓
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┗━━━━━━━━━┛
-   ┃                    ┃              
-   ┃                    ┃   ┏━━score━━┓
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┃  0      ┃
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┗━━━━━━━━━┛
-   ┃                    ┃              
-   ┃                    ┃   ┏━━lines━━┓
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┃  0      ┃
-   ┃                    ┃   ┃         ┃
-   ┃                    ┃   ┗━━━━━━━━━┛
-   ┗━━━━━━━━━━━━━━━━━━━━┛              
-                                       ";
+pub static SCREEN_STR: &str = "                                                             
+   ┏━━pnadon/tetris-rs━━┓                                    
+   ┃                    ┃                                    
+   ┃                    ┃   ┏━━next━━━┓ ┏━━stats━━━━━━━━━━━┓ 
+   ┃                    ┃   ┃         ┃ ┃                  ┃ 
+   ┃                    ┃   ┃         ┃ ┃                  ┃ 
+   ┃                    ┃   ┃         ┃ ┃                  ┃ 
+   ┃                    ┃   ┗━━━━━━━━━┛ ┃                  ┃ 
+   ┃                    ┃               ┃                  ┃ 
+   ┃                    ┃   ┏━━score━━┓ ┃                  ┃ 
+   ┃                    ┃   ┃  0      ┃ ┃                  ┃ 
+   ┃                    ┃   ┗━━━━━━━━━┛ ┃                  ┃ 
+   ┃                    ┃               ┃                  ┃ 
+   ┃                    ┃   ┏━━lines━━┓ ┃                  ┃ 
+   ┃                    ┃   ┃  0      ┃ ┃                  ┃ 
+   ┃                    ┃   ┗━━━━━━━━━┛ ┃                  ┃ 
+   ┃                    ┃               ┃                  ┃ 
+   ┃                    ┃   ┏━━level━━┓ ┗━━━━━━━━━━━━━━━━━━┛ 
+   ┃                    ┃   ┃  0      ┃                      
+   ┃                    ┃   ┗━━━━━━━━━┛                      
+   ┗━━━━━━━━━━━━━━━━━━━━┛                                    
+                                                             ";
 pub const NEXT_DISP_TL: Coord = Coord { row: 4, col: 29 };
-pub const SCORE_DISP_TL: Coord = Coord { row: 11, col: 30 };
-pub const LINES_DISP_TL: Coord = Coord { row: 17, col: 30 };
+pub const SCORE_DISP_TL: Coord = Coord { row: 10, col: 30 };
+pub const LINES_DISP_TL: Coord = Coord { row: 14, col: 30 };
+pub const LEVEL_DISP_TL: Coord = Coord { row: 18, col: 30 };
+pub const STATS_DISP_TL: Coord = Coord { row: 4, col: 42 };
+pub const ACTION_DISP_TL: Coord = Coord { row: 18, col: 41 };
+pub const STATUS_DISP_TL: Coord = Coord { row: 21, col: 4 };
 pub const DROP_DISP_TL: Coord = Coord { row: 0, col: 4 };
 pub const ARENA_TL: Coord = Coord { row: 2, col: 4 };
 pub const STAT_DIMS: Coord = Coord { row: 1, col: 7 };
 pub const ARENA_DIMS: Coord = Coord { row: 18, col: 20 };
 pub const NEXT_DIMS: Coord = Coord { row: 3, col: 9 };
+pub const STATS_DIMS: Coord = Coord { row: 13, col: 16 };
+pub const ACTION_DIMS: Coord = Coord { row: 3, col: 18 };
+pub const STATUS_DIMS: Coord = Coord { row: 1, col: 55 };
 
 fn in_area(row: usize, col: usize, tl: Coord, dims: Coord) -> bool {
     (row >= tl.row && row < tl.row + dims.row) && (col >= tl.col && col < tl.col + dims.col)
@@ -57,6 +65,10 @@ pub enum Display {
     Next,
     Score,
     Lines,
+    Level,
+    Stats,
+    Action,
+    Status,
     Arena,
     Drop,
 }
@@ -66,6 +78,10 @@ pub fn get_tl(disp: Display) -> Coord {
         Display::Next => NEXT_DISP_TL,
         Display::Score => SCORE_DISP_TL,
         Display::Lines => LINES_DISP_TL,
+        Display::Level => LEVEL_DISP_TL,
+        Display::Stats => STATS_DISP_TL,
+        Display::Action => ACTION_DISP_TL,
+        Display::Status => STATUS_DISP_TL,
         Display::Arena => ARENA_TL,
         Display::Drop => DROP_DISP_TL,
     }
@@ -75,10 +91,149 @@ pub fn get_dims(disp: Display) -> Coord {
     match disp {
         Display::Arena | Display::Drop => ARENA_DIMS,
         Display::Next => NEXT_DIMS,
+        Display::Stats => STATS_DIMS,
+        Display::Action => ACTION_DIMS,
+        Display::Status => STATUS_DIMS,
         _ => STAT_DIMS,
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scoring {
+    Nes,
+    Guideline,
+    Master,
+}
+
+pub fn to_scoring(name: &str) -> Scoring {
+    match name {
+        "guideline" => Scoring::Guideline,
+        _ => Scoring::Nes,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Progression {
+    Fixed,
+    Nes,
+    Variable,
+    Sections,
+}
+
+pub fn to_progression(name: &str) -> Progression {
+    match name {
+        "fixed" => Progression::Fixed,
+        "variable" => Progression::Variable,
+        _ => Progression::Nes,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineGravity {
+    // the rows above a clear move down together
+    Naive,
+    // groups of connected blocks fall on their own, which can clear more lines in a chain
+    Cascade,
+}
+
+pub fn to_line_gravity(name: &str) -> LineGravity {
+    match name {
+        "cascade" => LineGravity::Cascade,
+        _ => LineGravity::Naive,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Randomizer {
+    // any piece except the last one
+    Random,
+    // all seven pieces in a shuffled order, then the next seven
+    Bag,
+}
+
+pub fn to_randomizer(name: &str) -> Randomizer {
+    match name {
+        "bag" => Randomizer::Bag,
+        _ => Randomizer::Random,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StackVisibility {
+    Normal,
+    Invisible,
+    Fade(Duration),
+}
+
+pub fn to_stack_visibility(name: &str, fade_secs: u64) -> StackVisibility {
+    match name {
+        "invisible" => StackVisibility::Invisible,
+        "fade" => StackVisibility::Fade(Duration::from_secs(fade_secs)),
+        _ => StackVisibility::Normal,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Clear {
+    pub lines: usize,
+    pub tspin: TSpin,
+    pub combo: u32,
+    pub back_to_back: bool,
+    pub perfect: bool,
+    // how many clears in a row a cascade has caused, the first clear is not part of a chain
+    pub chain: u32,
+}
+
+impl Clear {
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && self.tspin != TSpin::None)
+    }
+
+    // the text shown in the action panel for this clear, most important first
+    pub fn popups(&self) -> Vec<String> {
+        let name = match self.lines {
+            0 => "",
+            1 => "SINGLE",
+            2 => "DOUBLE",
+            3 => "TRIPLE",
+            _ => "TETRIS",
+        };
+        let mut popups = Vec::new();
+        match self.tspin {
+            TSpin::Full => popups.push(format!("T-SPIN {}", name)),
+            TSpin::Mini => popups.push(format!("T-SPIN MINI {}", name)),
+            TSpin::None if self.lines >= 4 => popups.push(name.to_string()),
+            TSpin::None => (),
+        }
+        if self.back_to_back {
+            popups.push("B2B".to_string());
+        }
+        if self.combo > 0 {
+            popups.push(format!("{} COMBO", self.combo));
+        }
+        if self.chain > 0 {
+            popups.push(format!("{} CHAIN", self.chain + 1));
+        }
+        popups
+            .into_iter()
+            .map(|popup| popup.trim_end().to_string())
+            .collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    Clear(Clear),
+}
+
+#[derive(Copy, Clone)]
 pub enum Direction {
     Left,
     Right,
@@ -152,6 +307,17 @@ pub fn shape_color(shape_type: ShapeType) -> i16 {
     }
 }
 
+// garbage blocks use their own color pair after the seven shape colors
+pub const GARBAGE_COLOR: i16 = 8;
+
+pub fn garbage_color(num_colors: i32) -> i16 {
+    // bright black shows up as gray on terminals with more than the basic colors
+    match num_colors {
+        0..=8 => COLOR_WHITE,
+        _ => 8,
+    }
+}
+
 pub fn num_to_shape(num: i16) -> ShapeType {
     match num {
         1 => ShapeType::I,
@@ -165,6 +331,31 @@ pub fn num_to_shape(num: i16) -> ShapeType {
     }
 }
 
+pub fn to_shape_type(chr: char) -> Option<ShapeType> {
+    match chr.to_ascii_uppercase() {
+        'I' => Some(ShapeType::I),
+        'J' => Some(ShapeType::J),
+        'L' => Some(ShapeType::L),
+        'O' => Some(ShapeType::O),
+        'S' => Some(ShapeType::S),
+        'T' => Some(ShapeType::T),
+        'Z' => Some(ShapeType::Z),
+        _ => None,
+    }
+}
+
+pub fn shape_to_char(shape_type: ShapeType) -> char {
+    match shape_type {
+        ShapeType::I => 'I',
+        ShapeType::J => 'J',
+        ShapeType::L => 'L',
+        ShapeType::O => 'O',
+        ShapeType::S => 'S',
+        ShapeType::T => 'T',
+        ShapeType::Z => 'Z',
+    }
+}
+
 pub fn shape_to_num(shape_type: ShapeType) -> i16 {
     match shape_type {
         ShapeType::I => 1,
@@ -177,7 +368,15 @@ pub fn shape_to_num(shape_type: ShapeType) -> i16 {
     }
 }
 
-pub fn shape_coords(shape_type: ShapeType) -> [Coord; 4] {
+// a mirrored shape takes the coordinates of its mirror image
+pub fn shape_coords(shape_type: ShapeType, mirrored: bool) -> [Coord; 4] {
+    let shape_type = match (shape_type, mirrored) {
+        (ShapeType::J, true) => ShapeType::L,
+        (ShapeType::L, true) => ShapeType::J,
+        (ShapeType::S, true) => ShapeType::Z,
+        (ShapeType::Z, true) => ShapeType::S,
+        (shape_type, _) => shape_type,
+    };
     match shape_type {
         ShapeType::I => [
             Coord::new(1, 0),
@@ -224,12 +423,63 @@ pub fn shape_coords(shape_type: ShapeType) -> [Coord; 4] {
     }
 }
 
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Item {
+    // also clears the lines above and below
+    LineBomb,
+    // empties the column it is in
+    ColumnClear,
+    // turns the stack upside down
+    GravityFlip,
+    // swaps the next piece for another one
+    NextShuffle,
+}
+
+pub const ITEMS: [Item; 4] = [
+    Item::LineBomb,
+    Item::ColumnClear,
+    Item::GravityFlip,
+    Item::NextShuffle,
+];
+
+impl Item {
+    pub fn glyph(&self) -> char {
+        match self {
+            Item::LineBomb => '*',
+            Item::ColumnClear => '|',
+            Item::GravityFlip => '^',
+            Item::NextShuffle => '?',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Item::LineBomb => "LINE BOMB",
+            Item::ColumnClear => "COLUMN CLEAR",
+            Item::GravityFlip => "GRAVITY FLIP",
+            Item::NextShuffle => "SHUFFLE",
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Block {
+    pub color: i16,
+    pub item: Option<Item>,
+}
+
+impl Block {
+    pub fn new(color: i16) -> Self {
+        Self { color, item: None }
+    }
+}
+
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum Symbol {
     Data(char),
     Space,
-    LiveBlock(i16),
-    DeadBlock(i16),
+    LiveBlock(Block),
+    DeadBlock(Block),
     Text(char),
 }
 
@@ -245,7 +495,9 @@ pub fn from_symbol(sym: Symbol) -> char {
     match sym {
         Symbol::Data(num) => num,
         Symbol::Space => ' ',
-        Symbol::LiveBlock(_) | Symbol::DeadBlock(_) => '█',
+        Symbol::LiveBlock(block) | Symbol::DeadBlock(block) => {
+            block.item.map_or('█', |item| item.glyph())
+        }
         Symbol::Text(chr) => chr,
     }
 }
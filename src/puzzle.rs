@@ -0,0 +1,198 @@
+use serde::Deserialize;
+use std::fs;
+
+use crate::primitives::{
+    shape_to_num, to_shape_type, Block, ShapeType, Symbol, ARENA_DIMS, GARBAGE_COLOR,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Objective {
+    Lines(u32),
+    TspinTriple,
+    // a perfect clear within the given number of pieces
+    PerfectClear(u32),
+}
+
+impl Objective {
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Lines(1) => "clear 1 line".to_string(),
+            Objective::Lines(lines) => format!("clear {} lines", lines),
+            Objective::TspinTriple => "T-spin triple".to_string(),
+            Objective::PerfectClear(pieces) => format!("perfect clear in {}", pieces),
+        }
+    }
+}
+
+// a single puzzle, where the board is drawn top to bottom with one character per block:
+// '.' or ' ' is empty, a piece letter is a block of that color, anything else is garbage
+#[derive(Clone, Debug, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    pub pieces: String,
+    pub objective: Objective,
+    #[serde(default)]
+    pub board: String,
+}
+
+#[derive(Deserialize)]
+struct PuzzlePack {
+    puzzle: Vec<Puzzle>,
+}
+
+impl Puzzle {
+    pub fn shapes(&self) -> Vec<ShapeType> {
+        self.pieces.chars().filter_map(to_shape_type).collect()
+    }
+
+    // the number of pieces that can be placed before the puzzle is failed
+    pub fn piece_limit(&self) -> u32 {
+        let pieces = self.shapes().len() as u32;
+        match self.objective {
+            Objective::PerfectClear(limit) => pieces.min(limit),
+            _ => pieces,
+        }
+    }
+
+    pub fn board(&self) -> Vec<Vec<Symbol>> {
+        to_board(&self.board)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.shapes().is_empty() {
+            return Err(format!("puzzle \"{}\" has no pieces", self.name));
+        }
+        if let Some(chr) = self
+            .pieces
+            .chars()
+            .find(|chr| !chr.is_whitespace() && to_shape_type(*chr).is_none())
+        {
+            return Err(format!(
+                "puzzle \"{}\" has unknown piece {}",
+                self.name, chr
+            ));
+        }
+        if let Objective::Lines(0) | Objective::PerfectClear(0) = self.objective {
+            return Err(format!("puzzle \"{}\" has an empty objective", self.name));
+        }
+        validate_board("puzzle", &self.name, &self.board)
+    }
+}
+
+// a board to start perfect clear attempts from, in the same format as puzzle boards.
+// `pieces` is how many pieces it takes to finish, otherwise --pc-pieces is used
+#[derive(Clone, Debug, Deserialize)]
+pub struct Setup {
+    pub name: String,
+    pub board: String,
+    pub pieces: Option<u32>,
+}
+
+impl Setup {
+    pub fn board(&self) -> Vec<Vec<Symbol>> {
+        to_board(&self.board)
+    }
+}
+
+#[derive(Deserialize)]
+struct SetupBook {
+    setup: Vec<Setup>,
+}
+
+pub fn to_board(board: &str) -> Vec<Vec<Symbol>> {
+    board_rows(board)
+        .map(|row| {
+            let mut blocks = row
+                .chars()
+                .map(|chr| match (chr, to_shape_type(chr)) {
+                    ('.' | ' ', _) => Symbol::Space,
+                    (_, Some(shape_type)) => {
+                        Symbol::DeadBlock(Block::new(shape_to_num(shape_type)))
+                    }
+                    (_, None) => Symbol::DeadBlock(Block::new(GARBAGE_COLOR)),
+                })
+                .collect::<Vec<Symbol>>();
+            blocks.resize(ARENA_DIMS.col / 2, Symbol::Space);
+            blocks
+        })
+        .collect()
+}
+
+fn board_rows(board: &str) -> impl Iterator<Item = &str> {
+    board
+        .lines()
+        .map(|row| row.trim_end())
+        .skip_while(|row| row.is_empty())
+}
+
+fn validate_board(kind: &str, name: &str, board: &str) -> Result<(), String> {
+    if board_rows(board).count() >= ARENA_DIMS.row {
+        return Err(format!("{} \"{}\" has too many rows", kind, name));
+    }
+    if board_rows(board).any(|row| row.chars().count() > ARENA_DIMS.col / 2) {
+        return Err(format!("{} \"{}\" has a row that is too wide", kind, name));
+    }
+    Ok(())
+}
+
+pub fn load_puzzles(path: &str) -> Result<Vec<Puzzle>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let pack: PuzzlePack = toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    if pack.puzzle.is_empty() {
+        return Err(format!("{}: no puzzles found", path));
+    }
+    for puzzle in pack.puzzle.iter() {
+        puzzle.validate()?;
+    }
+    Ok(pack.puzzle)
+}
+
+pub fn load_setup(path: &str, name: &str) -> Result<Setup, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let book: SetupBook = toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    let setup = book
+        .setup
+        .iter()
+        .find(|setup| setup.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let names = book
+                .setup
+                .iter()
+                .map(|setup| setup.name.as_str())
+                .collect::<Vec<&str>>();
+            format!(
+                "{}: no setup named {}, try one of: {}",
+                path,
+                name,
+                names.join(", ")
+            )
+        })?;
+    if setup.pieces == Some(0) {
+        return Err(format!("setup \"{}\" has no pieces", setup.name));
+    }
+    validate_board("setup", &setup.name, &setup.board)?;
+    Ok(setup.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/puzzles/basics.toml");
+    const SETUPS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/setups/pc.toml");
+
+    #[test]
+    fn loads_the_bundled_puzzles() {
+        assert_eq!(load_puzzles(PUZZLES).map(|puzzles| puzzles.len()), Ok(3));
+    }
+
+    #[test]
+    fn loads_every_bundled_setup() {
+        let book: SetupBook = toml::from_str(&fs::read_to_string(SETUPS).unwrap()).unwrap();
+        assert!(!book.setup.is_empty());
+        for setup in book.setup.iter() {
+            assert!(load_setup(SETUPS, &setup.name).is_ok(), "{}", setup.name);
+        }
+    }
+}
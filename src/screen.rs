@@ -1,24 +1,43 @@
 use crate::shape::Shape;
-use ncurses::{addstr, attrset, mvaddstr, refresh, stdscr, wmove, COLOR_PAIR};
+use crate::stats::format_stat;
+use ncurses::{
+    addstr, attr_t, attroff, attron, attrset, mvaddstr, mvprintw, refresh, stdscr, wmove, A_BOLD,
+    A_DIM, A_NORMAL, A_REVERSE, COLOR_PAIR,
+};
+use std::time::Instant;
 use std::{thread, time};
 
 use crate::primitives::{
-    arena_row_iter, from_symbol, get_dims, get_tl, in_arena, in_next_disp, to_symbol, Coord,
-    Display, Symbol, SCREEN_STR,
+    arena_row_iter, from_symbol, get_dims, get_tl, in_arena, in_next_disp, to_symbol, Block, Coord,
+    Display, Item, StackVisibility, Symbol, ARENA_DIMS, ARENA_TL, GARBAGE_COLOR, SCREEN_STR,
 };
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+struct Popup {
+    text: String,
+    shown_at: Instant,
+    duration: time::Duration,
+}
+
+#[derive(Clone, Debug)]
 pub struct Screen {
     contents: Vec<Vec<Symbol>>,
+    locked_at: Vec<Vec<Option<Instant>>>,
+    stack: StackVisibility,
+    popups: Vec<Popup>,
 }
 
 impl Screen {
-    pub fn new() -> Self {
+    pub fn new(stack: StackVisibility) -> Self {
+        let contents = SCREEN_STR
+            .lines()
+            .map(|line| line.chars().map(to_symbol).collect::<Vec<Symbol>>())
+            .collect::<Vec<Vec<Symbol>>>();
         Self {
-            contents: SCREEN_STR
-                .lines()
-                .map(|line| line.chars().map(to_symbol).collect::<Vec<Symbol>>())
-                .collect::<Vec<Vec<Symbol>>>(),
+            locked_at: contents.iter().map(|row| vec![None; row.len()]).collect(),
+            contents,
+            stack,
+            popups: Vec::new(),
         }
     }
 
@@ -68,14 +87,27 @@ impl Screen {
                 let cur = self.get_cell(row, col);
                 // "magic numbers", checks if inside of game window, should be replaced
                 if in_arena(row, col) || in_next_disp(row, col) {
-                    match cur {
-                        Symbol::DeadBlock(num) | Symbol::LiveBlock(num) => {
-                            attrset(COLOR_PAIR(num));
+                    match (cur, self.block_attr(row, col)) {
+                        (Symbol::DeadBlock(_), None) => {
+                            mvaddstr(
+                                row as i32,
+                                col as i32,
+                                &from_symbol(Symbol::Space).to_string(),
+                            );
+                        }
+                        (Symbol::DeadBlock(block), Some(attr))
+                        | (Symbol::LiveBlock(block), Some(attr)) => {
+                            // items stand out with the colors swapped
+                            let attr = match block.item {
+                                Some(_) => attr | A_REVERSE(),
+                                None => attr,
+                            };
+                            attrset(COLOR_PAIR(block.color) | attr);
 
                             mvaddstr(row as i32, col as i32, &from_symbol(cur).to_string());
                             attrset(COLOR_PAIR(1));
                         }
-                        Symbol::Space => {
+                        (Symbol::Space, _) => {
                             mvaddstr(row as i32, col as i32, &from_symbol(cur).to_string());
                         }
                         _ => (),
@@ -88,11 +120,36 @@ impl Screen {
         wmove(stdscr(), 0, 0);
     }
 
+    // the attribute to draw a block with, or None while it is hidden.
+    // only blocks placed by the player are affected, garbage always stays visible
+    fn block_attr(&self, row: usize, col: usize) -> Option<attr_t> {
+        let age = match self.locked_at[row][col] {
+            Some(locked_at) => locked_at.elapsed(),
+            None => return Some(A_NORMAL()),
+        };
+        match self.stack {
+            StackVisibility::Normal => Some(A_NORMAL()),
+            StackVisibility::Invisible => None,
+            StackVisibility::Fade(duration) if age >= duration => None,
+            StackVisibility::Fade(duration) if age >= duration * 3 / 4 => Some(A_DIM()),
+            StackVisibility::Fade(_) => Some(A_NORMAL()),
+        }
+    }
+
+    pub fn reveal(&mut self) {
+        self.stack = StackVisibility::Normal;
+    }
+
+    fn move_cell(&mut self, from: Coord, to: Coord) {
+        self.contents[to.row][to.col] = self.contents[from.row][from.col];
+        self.locked_at[to.row][to.col] = self.locked_at[from.row][from.col];
+    }
+
     pub fn add_next(&mut self, shape: &Shape) {
         self.wipe_display(Display::Next);
-        for coord in shape.coords().iter() {
+        for (coord, symbol) in shape.blocks() {
             if in_next_disp(coord.row, coord.col) {
-                self.set_cell(*coord, shape.symbol());
+                self.set_cell(coord, symbol);
             } else {
                 panic!(
                     "invalid shape & coords for add_next: {:?}\n{:?}",
@@ -116,23 +173,66 @@ impl Screen {
         wmove(stdscr(), 0, 0);
     }
 
-    pub fn update_stat_display(&mut self, stat: u32, disp: Display) {
+    pub fn update_stat_display(&mut self, stat: u64, disp: Display) {
         self.wipe_display(disp);
-        for (idx, chr) in stat.to_string().chars().enumerate() {
+        for (idx, chr) in format_stat(stat, get_dims(disp).col).chars().enumerate() {
             self.set_disp_cell(Coord::new(0, idx), disp, Symbol::Data(chr));
         }
     }
 
+    pub fn update_text_display(&mut self, rows: &[String], disp: Display) {
+        self.wipe_display(disp);
+        for (row, text) in rows.iter().take(get_dims(disp).row).enumerate() {
+            for (idx, chr) in text.chars().take(get_dims(disp).col).enumerate() {
+                self.set_disp_cell(Coord::new(row, idx), disp, Symbol::Data(chr));
+            }
+        }
+    }
+
+    pub fn add_popups(&mut self, texts: &[String], duration: time::Duration) {
+        let shown_at = Instant::now();
+        for text in texts.iter().rev() {
+            self.popups.insert(
+                0,
+                Popup {
+                    text: text.clone(),
+                    shown_at,
+                    duration,
+                },
+            );
+        }
+        self.popups.truncate(get_dims(Display::Action).row);
+    }
+
+    // newest popups are drawn at the top, bold at first and dimmed shortly before they expire
+    pub fn draw_popups(&mut self) {
+        self.popups
+            .retain(|popup| popup.shown_at.elapsed() < popup.duration);
+
+        let tl = get_tl(Display::Action);
+        for (row, popup) in self.popups.iter().enumerate() {
+            let progress = popup.shown_at.elapsed().as_secs_f64() / popup.duration.as_secs_f64();
+            let attr = match progress {
+                p if p < 0.5 => A_BOLD(),
+                p if p < 0.8 => 0,
+                _ => A_DIM(),
+            };
+            attron(attr);
+            mvaddstr((tl.row + row) as i32, tl.col as i32, &popup.text);
+            attroff(attr);
+        }
+        wmove(stdscr(), 0, 0);
+    }
+
     pub fn set_shape(&mut self, shape: Shape) {
-        assert!(match shape.display() {
-            Display::Drop => true,
-            Display::Arena => true,
-            _ => false,
-        });
+        assert!(matches!(shape.display(), Display::Drop | Display::Arena));
         assert!(shape.is_dead());
         // assert!(self.curr_shape.is_dead());
-        for coord in shape.coords().iter() {
-            self.set_cell(*coord, shape.symbol());
+        let locked_at = Instant::now();
+        for (coord, symbol) in shape.blocks() {
+            self.set_cell(coord, symbol);
+            self.locked_at[coord.row][coord.col] = Some(locked_at);
+            self.locked_at[coord.row][coord.col + 1] = Some(locked_at);
         }
     }
 
@@ -145,11 +245,7 @@ impl Screen {
         wmove(stdscr(), 0, 0);
     }
 
-    pub fn shift_lines(&mut self, lines: &[usize]) {
-        if lines.is_empty() {
-            return;
-        }
-
+    fn flash_lines(&mut self, lines: &[usize]) {
         self.disp_flash(lines);
 
         refresh();
@@ -161,18 +257,382 @@ impl Screen {
         self.disp_flash(lines);
         refresh();
         thread::sleep(time::Duration::from_millis(45));
+    }
+
+    pub fn shift_lines(&mut self, lines: &[usize]) {
+        if lines.is_empty() {
+            return;
+        }
+
+        self.flash_lines(lines);
 
         // optimize, use circular array w/ pointer
         for line in lines.iter() {
             for row in (get_tl(Display::Arena).row..*line).rev() {
                 for col in arena_row_iter() {
-                    self.contents[row + 1][col] = self.contents[row][col];
+                    self.move_cell(Coord::new(row, col), Coord::new(row + 1, col));
                 }
             }
         }
     }
 
+    // empties the lines without moving anything above them, see settle
+    pub fn empty_lines(&mut self, lines: &[usize]) {
+        if lines.is_empty() {
+            return;
+        }
+
+        self.flash_lines(lines);
+        for row in lines.iter() {
+            for col in arena_row_iter() {
+                self.set_space(*row, col);
+                self.locked_at[*row][col] = None;
+            }
+        }
+    }
+
+    // lets every group of connected blocks fall on its own until it lands
+    pub fn settle(&mut self) {
+        while self.drop_groups() {
+            self.draw();
+            refresh();
+            thread::sleep(time::Duration::from_millis(25));
+        }
+    }
+
+    // moves each group of connected blocks with nothing underneath down a row,
+    // starting from the lowest. returns false once every group has landed
+    fn drop_groups(&mut self) -> bool {
+        let bottom = ARENA_TL.row + ARENA_DIMS.row;
+        let mut groups = self.block_groups();
+        groups.sort_by_key(|group| std::cmp::Reverse(group[0].row));
+
+        let mut dropped = false;
+        for group in groups.iter() {
+            let can_fall = group.iter().all(|cell| {
+                cell.row + 1 < bottom
+                    && (self.is_space(cell.row + 1, cell.col)
+                        || group.contains(&Coord::new(cell.row + 1, cell.col)))
+            });
+            if can_fall {
+                // the group is sorted bottom up, so no block is moved onto another one
+                for cell in group.iter() {
+                    self.move_cell(*cell, Coord::new(cell.row + 1, cell.col));
+                    self.set_space(cell.row, cell.col);
+                    self.locked_at[cell.row][cell.col] = None;
+                }
+                dropped = true;
+            }
+        }
+        dropped
+    }
+
+    // the blocks in the arena grouped by which ones touch, each sorted from the bottom up
+    fn block_groups(&self) -> Vec<Vec<Coord>> {
+        let mut seen =
+            vec![vec![false; ARENA_TL.col + ARENA_DIMS.col]; ARENA_TL.row + ARENA_DIMS.row];
+        let mut groups = Vec::new();
+        for row in ARENA_TL.row..(ARENA_TL.row + ARENA_DIMS.row) {
+            for col in arena_row_iter() {
+                if seen[row][col] || self.is_space(row, col) {
+                    continue;
+                }
+                seen[row][col] = true;
+                let mut group = Vec::new();
+                let mut stack = vec![Coord::new(row, col)];
+                while let Some(cell) = stack.pop() {
+                    group.push(cell);
+                    let neighbours = [
+                        Coord::new(cell.row - 1, cell.col),
+                        Coord::new(cell.row + 1, cell.col),
+                        Coord::new(cell.row, cell.col - 1),
+                        Coord::new(cell.row, cell.col + 1),
+                    ];
+                    for next in neighbours.iter() {
+                        if in_arena(next.row, next.col)
+                            && !seen[next.row][next.col]
+                            && !self.is_space(next.row, next.col)
+                        {
+                            seen[next.row][next.col] = true;
+                            stack.push(*next);
+                        }
+                    }
+                }
+                group.sort_by_key(|cell| std::cmp::Reverse(cell.row));
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    // the items in the given lines, with the block holding each one
+    pub fn items(&self, lines: &[usize]) -> Vec<(Coord, Item)> {
+        let mut items = Vec::new();
+        for row in lines.iter() {
+            for col in arena_row_iter().step_by(2) {
+                if let Symbol::DeadBlock(Block {
+                    item: Some(item), ..
+                }) = self.get_cell(*row, col)
+                {
+                    items.push((Coord::new(*row, col), item));
+                }
+            }
+        }
+        items
+    }
+
+    // empties the `width` characters of the arena starting at `col`
+    pub fn clear_column(&mut self, col: usize, width: usize) {
+        for row in ARENA_TL.row..(ARENA_TL.row + ARENA_DIMS.row) {
+            for col in (col..col + width).step_by(2) {
+                self.set_block(Coord::new(row, col), Symbol::Space);
+            }
+        }
+    }
+
+    // turns the rows from the top of the stack down to the floor upside down
+    pub fn flip_stack(&mut self) {
+        let bottom = ARENA_TL.row + ARENA_DIMS.row;
+        let top = (ARENA_TL.row..bottom)
+            .find(|row| arena_row_iter().any(|col| !self.is_space(*row, col)))
+            .unwrap_or(bottom);
+        for idx in 0..(bottom - top) / 2 {
+            let (upper, lower) = (top + idx, bottom - 1 - idx);
+            for col in arena_row_iter() {
+                let cell = self.contents[upper][col];
+                self.contents[upper][col] = self.contents[lower][col];
+                self.contents[lower][col] = cell;
+                let locked_at = self.locked_at[upper][col];
+                self.locked_at[upper][col] = self.locked_at[lower][col];
+                self.locked_at[lower][col] = locked_at;
+            }
+        }
+    }
+
+    // whether clearing the given lines would leave the arena empty
+    pub fn only_lines_left(&self, lines: &[usize]) -> bool {
+        let start = get_tl(Display::Arena);
+        let end = start + get_dims(Display::Arena);
+        (start.row..end.row)
+            .filter(|row| !lines.contains(row))
+            .all(|row| (start.col..end.col).all(|col| self.is_space(row, col)))
+    }
+
+    pub fn disp_banner(&mut self, text: &str) {
+        let row = (ARENA_TL.row + ARENA_DIMS.row / 2) as i32;
+        let col = (ARENA_TL.col + (ARENA_DIMS.col - text.chars().count()) / 2) as i32;
+
+        for _ in 0..3 {
+            mvaddstr(row, col, text);
+            refresh();
+            thread::sleep(time::Duration::from_millis(150));
+            self.draw();
+            refresh();
+            thread::sleep(time::Duration::from_millis(75));
+        }
+    }
+
+    // a box across the arena with each line of text centered inside of it
+    // the dialog has to fit inside the arena, so lines past that are dropped except for the last
+    pub fn disp_dialog(&mut self, lines: &[String]) {
+        let max_lines = ARENA_DIMS.row - 2;
+        let lines = match lines.split_last() {
+            Some((last, rest)) if lines.len() > max_lines => {
+                let mut kept = rest[..max_lines - 1].to_vec();
+                kept.push(last.clone());
+                kept
+            }
+            _ => lines.to_vec(),
+        };
+        let top = (ARENA_TL.row + (ARENA_DIMS.row - lines.len()) / 2 - 2) as i32;
+        let bottom = top + lines.len() as i32 + 1;
+        let left = ARENA_TL.col as i32 - 1;
+        let right = (ARENA_TL.col + ARENA_DIMS.col) as i32;
+
+        for row in top..=bottom {
+            for col in (left + 1)..right {
+                mvprintw(row, col, " ");
+            }
+        }
+        for col in (left + 1)..right {
+            mvprintw(top, col, "━");
+            mvprintw(bottom, col, "━");
+        }
+        mvprintw(top, left, "┣");
+        mvprintw(bottom, left, "┣");
+        mvprintw(top, right, "┫");
+        mvprintw(bottom, right, "┫");
+
+        self.top();
+
+        for (idx, line) in lines.iter().enumerate() {
+            let col = ARENA_TL.col + ARENA_DIMS.col.saturating_sub(line.chars().count()) / 2;
+            mvprintw(top + 1 + idx as i32, col as i32, line);
+        }
+    }
+
+    // pushes the stack up by one row per hole, filling the bottom with garbage that has a
+    // single gap at each given column. returns false if any block was pushed out of the arena
+    pub fn add_garbage(&mut self, holes: &[usize]) -> bool {
+        let start = get_tl(Display::Arena);
+        let end = start + get_dims(Display::Arena);
+        let rows = holes.len();
+
+        let overflows = (start.row..(start.row + rows).min(end.row))
+            .any(|row| arena_row_iter().any(|col| !self.is_space(row, col)));
+
+        for row in start.row..end.row {
+            for col in arena_row_iter() {
+                match row + rows {
+                    below if below < end.row => {
+                        self.move_cell(Coord::new(below, col), Coord::new(row, col))
+                    }
+                    _ => {
+                        self.contents[row][col] = Symbol::Space;
+                        self.locked_at[row][col] = None;
+                    }
+                }
+            }
+        }
+        for (idx, hole) in holes.iter().enumerate() {
+            let row = end.row - rows + idx;
+            for col in (0..ARENA_DIMS.col / 2).filter(|col| col != hole) {
+                self.set_cell(
+                    Coord::new(row, start.col + col * 2),
+                    Symbol::DeadBlock(Block::new(GARBAGE_COLOR)),
+                );
+            }
+        }
+        !overflows
+    }
+
+    // replaces the whole arena with the given rows of blocks, one symbol per block,
+    // lined up against the bottom of the arena
+    pub fn set_board(&mut self, board: &[Vec<Symbol>]) {
+        let start = get_tl(Display::Arena);
+        let end = start + get_dims(Display::Arena);
+        for row in start.row..end.row {
+            for col in arena_row_iter() {
+                self.contents[row][col] = Symbol::Space;
+                self.locked_at[row][col] = None;
+            }
+        }
+        for (idx, blocks) in board.iter().enumerate() {
+            let row = end.row - board.len() + idx;
+            for (col, block) in blocks.iter().enumerate() {
+                self.set_cell(Coord::new(row, start.col + col * 2), *block);
+            }
+        }
+    }
+
+    // paints or erases a single block, as placed by hand rather than by a piece
+    pub fn set_block(&mut self, coord: Coord, block: Symbol) {
+        for col in coord.col..(coord.col + 2) {
+            self.contents[coord.row][col] = block;
+            self.locked_at[coord.row][col] = None;
+        }
+    }
+
+    pub fn garbage_rows(&self) -> usize {
+        let start = get_tl(Display::Arena);
+        let end = start + get_dims(Display::Arena);
+        (start.row..end.row)
+            .filter(|row| {
+                arena_row_iter().any(|col| {
+                    matches!(self.get_cell(*row, col), Symbol::DeadBlock(block) if block.color == GARBAGE_COLOR)
+                })
+            })
+            .count()
+    }
+
     pub fn contents(&self) -> &Vec<Vec<Symbol>> {
         &self.contents
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::to_board;
+
+    fn screen(board: &str) -> Screen {
+        let mut screen = Screen::new(StackVisibility::Normal);
+        screen.set_board(&to_board(board));
+        screen
+    }
+
+    // the bottom rows of the arena, with 'X' for every block
+    fn bottom_rows(screen: &Screen, rows: usize) -> Vec<String> {
+        let bottom = ARENA_TL.row + ARENA_DIMS.row;
+        ((bottom - rows)..bottom)
+            .map(|row| {
+                arena_row_iter()
+                    .step_by(2)
+                    .map(|col| match screen.is_space(row, col) {
+                        true => '.',
+                        false => 'X',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_touching_blocks() {
+        let screen = screen(
+            "
+S.........
+SS..ZZ....
+.S...ZZ..X
+",
+        );
+        let mut groups = screen.block_groups();
+        groups.sort_by_key(|group| group.len());
+        let sizes = groups.iter().map(|group| group.len()).collect::<Vec<_>>();
+        // every block is two cells wide
+        assert_eq!(sizes, vec![2, 8, 8]);
+        for group in groups.iter() {
+            assert!(group.windows(2).all(|pair| pair[0].row >= pair[1].row));
+        }
+    }
+
+    #[test]
+    fn groups_fall_a_row_at_a_time_until_they_land() {
+        let mut screen = screen(
+            "
+ZZ........
+..........
+SS........
+..........
+",
+        );
+        assert!(screen.drop_groups());
+        assert_eq!(
+            bottom_rows(&screen, 4),
+            vec!["..........", "XX........", "..........", "XX........"]
+        );
+        assert!(screen.drop_groups());
+        assert_eq!(
+            bottom_rows(&screen, 4),
+            vec!["..........", "..........", "XX........", "XX........"]
+        );
+        assert!(!screen.drop_groups());
+    }
+
+    #[test]
+    fn supported_groups_stay_put() {
+        let mut screen = screen(
+            "
+JJJ.......
+I.....OO..
+I.....OO..
+",
+        );
+        // the overhang is held up by the column it is connected to
+        assert!(!screen.drop_groups());
+        assert_eq!(
+            bottom_rows(&screen, 3),
+            vec!["XXX.......", "X.....XX..", "X.....XX.."]
+        );
+    }
+}
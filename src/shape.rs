@@ -1,8 +1,9 @@
-use rand::prelude::random;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
 
 use crate::primitives::{
-    get_dims, get_tl, num_to_shape, shape_coords, shape_to_num, Coord, Display, ShapeType, Symbol,
-    BLOCK_HORIZ_MULT,
+    get_dims, get_tl, num_to_shape, shape_coords, shape_to_num, Block, Coord, Direction, Display,
+    Item, ShapeType, Symbol, BLOCK_HORIZ_MULT,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -12,32 +13,52 @@ pub struct Shape {
     tl_coords: Coord,
     display: Display,
     is_dead: bool,
+    scale: usize,
+    mirrored: bool,
+    // the block of the shape carrying an item, if any
+    item: Option<(usize, Item)>,
 }
 
 impl Shape {
-    pub fn new() -> Self {
-        let choice = random::<usize>() % 7 + 1;
-        let shape_type = num_to_shape(choice as i16);
+    pub fn new(rng: &mut ChaCha8Rng) -> Self {
+        let choice = rng.gen::<u32>() % 7 + 1;
+        Self::from_type(num_to_shape(choice as i16))
+    }
+
+    pub fn from_type(shape_type: ShapeType) -> Self {
         Self {
             clockwise_rotations: 0,
             shape_type,
             tl_coords: get_tl(Display::Next),
             display: Display::Next,
             is_dead: false,
+            scale: 1,
+            mirrored: false,
+            item: None,
         }
     }
 
-    pub fn coords(&self) -> [Coord; 4] {
-        let mut coords = shape_coords(self.shape_type);
-        for i in 0..4 {
+    // each block of a scaled shape covers `scale` by `scale` cells
+    pub fn coords(&self) -> Vec<Coord> {
+        let mut coords = Vec::with_capacity(4 * self.scale * self.scale);
+        for coord in shape_coords(self.shape_type, self.mirrored).iter() {
+            let mut coord = *coord;
             for _ in 0..self.clockwise_rotations {
-                coords[i] = coords[i].rotate(match self.shape_type {
+                coord = coord.rotate(match self.shape_type {
                     ShapeType::I => 4,
                     _ => 3,
                 });
             }
-            coords[i] =
-                Coord::new(coords[i].row, coords[i].col * BLOCK_HORIZ_MULT) + self.tl_coords;
+            for row in 0..self.scale {
+                for col in 0..self.scale {
+                    coords.push(
+                        Coord::new(
+                            coord.row * self.scale + row,
+                            (coord.col * self.scale + col) * BLOCK_HORIZ_MULT,
+                        ) + self.tl_coords,
+                    );
+                }
+            }
         }
         coords
     }
@@ -49,16 +70,51 @@ impl Shape {
         self.clockwise_rotations = (self.clockwise_rotations + 1) % 4;
     }
 
+    // the shape rotated right, kicked one column to the side if it does not fit, trying the
+    // right first when mirrored. `shift` gives the shape one column over if it fits there
+    pub fn kicked_rotation(
+        &self,
+        fits: impl Fn(&Shape) -> bool,
+        shift: impl Fn(&Shape, Direction) -> Option<Shape>,
+    ) -> Option<Shape> {
+        let mut rotated = *self;
+        rotated.rotate_right();
+        if fits(&rotated) {
+            return Some(rotated);
+        }
+        let kicks = match self.mirrored {
+            true => [Direction::Right, Direction::Left],
+            false => [Direction::Left, Direction::Right],
+        };
+        kicks.iter().find_map(|dir| shift(&rotated, *dir))
+    }
+
     pub fn move_right(&mut self) {
-        self.tl_coords.col += 2;
+        self.tl_coords.col += BLOCK_HORIZ_MULT * self.scale;
     }
 
     pub fn move_left(&mut self) {
-        self.tl_coords.col -= 2;
+        self.tl_coords.col -= BLOCK_HORIZ_MULT * self.scale;
+    }
+
+    pub fn move_up(&mut self) {
+        self.tl_coords.row -= self.scale;
     }
 
     pub fn move_down(&mut self) {
-        self.tl_coords.row += 1;
+        self.tl_coords.row += self.scale;
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: usize) {
+        self.scale = scale;
+    }
+
+    pub fn set_mirrored(&mut self, mirrored: bool) {
+        self.mirrored = mirrored;
     }
 
     pub fn tl_coords(&self) -> Coord {
@@ -88,11 +144,31 @@ impl Shape {
         shape_to_num(self.shape_type)
     }
 
-    pub fn symbol(&self) -> Symbol {
-        match self.is_dead {
-            true => Symbol::DeadBlock(self.color_num()),
-            false => Symbol::LiveBlock(self.color_num()),
-        }
+    // each cell of the shape with what is drawn there. an item is only carried by the first cell
+    // of its block, so a scaled block still holds a single item
+    pub fn blocks(&self) -> Vec<(Coord, Symbol)> {
+        let cells = self.scale * self.scale;
+        self.coords()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, coord)| {
+                let block = Block {
+                    color: self.color_num(),
+                    item: self
+                        .item
+                        .filter(|(block, _)| idx == block * cells)
+                        .map(|(_, item)| item),
+                };
+                match self.is_dead {
+                    true => (coord, Symbol::DeadBlock(block)),
+                    false => (coord, Symbol::LiveBlock(block)),
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_item(&mut self, block: usize, item: Item) {
+        self.item = Some((block, item));
     }
 
     pub fn change_display(&mut self, disp: Display, adjust_coords: bool) {
@@ -105,13 +181,15 @@ impl Shape {
     pub fn center(&mut self) {
         self.tl_coords.col =
             get_tl(self.display).col + get_dims(self.display).col / 2 - self.shape_width() / 2;
-        self.tl_coords.col -= self.tl_coords.col % 2;
+        // keep scaled shapes on their own coarser grid within the display
+        self.tl_coords.col -=
+            (self.tl_coords.col - get_tl(self.display).col) % (BLOCK_HORIZ_MULT * self.scale);
     }
 
-    pub fn descent_coords(&self, descent: usize) -> [Coord; 4] {
+    pub fn descent_coords(&self, descent: usize) -> Vec<Coord> {
         let mut coords = self.coords();
-        for i in 0..coords.len() {
-            coords[i] = Coord::new(coords[i].row + descent, coords[i].col)
+        for coord in coords.iter_mut() {
+            coord.row += descent * self.scale;
         }
         coords
     }
@@ -128,7 +206,54 @@ impl Shape {
         self.is_dead
     }
 
+    pub fn rotations(&self) -> u8 {
+        self.clockwise_rotations
+    }
+
     pub fn shape_type(&self) -> ShapeType {
         self.shape_type
     }
 }
+
+// one of each shape in a random order
+pub fn shuffled_bag(rng: &mut ChaCha8Rng) -> Vec<ShapeType> {
+    let mut bag = (1..=7).map(num_to_shape).collect::<Vec<ShapeType>>();
+    shuffle(&mut bag, rng);
+    bag
+}
+
+// fisher-yates drawing only u32s, so a seed gives the same order on every platform
+pub fn shuffle<T>(items: &mut [T], rng: &mut ChaCha8Rng) {
+    for idx in (1..items.len()).rev() {
+        let other = rng.gen::<u32>() % (idx as u32 + 1);
+        items.swap(idx, other as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::shape_to_char;
+    use rand::SeedableRng;
+
+    // pins the generator and the shuffle, so seeded games keep dealing the same pieces
+    #[test]
+    fn a_seed_always_deals_the_same_bags() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2024);
+        let bags = (0..3)
+            .flat_map(|_| shuffled_bag(&mut rng))
+            .map(shape_to_char)
+            .collect::<String>();
+        assert_eq!(bags, "IZJSLTOTOSJZLIOISJLZT");
+    }
+
+    #[test]
+    fn shuffle_keeps_every_item() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut items = (0..50).collect::<Vec<u32>>();
+        shuffle(&mut items, &mut rng);
+        assert_ne!(items, (0..50).collect::<Vec<u32>>());
+        items.sort_unstable();
+        assert_eq!(items, (0..50).collect::<Vec<u32>>());
+    }
+}
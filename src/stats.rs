@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use crate::primitives::{num_to_shape, shape_to_num, ShapeType, STATS_DIMS};
+
+pub const SPLIT_LINES: u32 = 10;
+const CLEAR_NAMES: [&str; 4] = ["single", "double", "triple", "tetris"];
+
+#[derive(Debug)]
+pub struct Stats {
+    start: Option<Instant>,
+    end: Option<Instant>,
+    pieces: u32,
+    lines: u32,
+    splits: Vec<Duration>,
+    keys: u32,
+    faults: u32,
+    clears: [u32; 4],
+    shapes: [u32; 7],
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+            pieces: 0,
+            lines: 0,
+            splits: Vec::new(),
+            keys: 0,
+            faults: 0,
+            clears: [0; 4],
+            shapes: [0; 7],
+        }
+    }
+
+    // starts the clock unless it is already running, the first key press also starts it
+    pub fn start(&mut self) {
+        self.start.get_or_insert_with(Instant::now);
+    }
+
+    pub fn add_key(&mut self) {
+        self.start();
+        self.keys += 1;
+    }
+
+    pub fn stop(&mut self) {
+        self.end.get_or_insert_with(Instant::now);
+    }
+
+    pub fn add_piece(&mut self, shape_type: ShapeType) {
+        self.pieces += 1;
+        self.shapes[shape_to_num(shape_type) as usize - 1] += 1;
+    }
+
+    pub fn add_fault(&mut self) {
+        self.faults += 1;
+    }
+
+    pub fn faults(&self) -> u32 {
+        self.faults
+    }
+
+    pub fn add_clear(&mut self, rows_filled: usize) {
+        if rows_filled == 0 {
+            return;
+        }
+        self.clears[rows_filled.min(4) - 1] += 1;
+        self.lines += rows_filled as u32;
+        while self.lines >= (self.splits.len() as u32 + 1) * SPLIT_LINES {
+            self.splits.push(self.elapsed());
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => end - start,
+            (Some(start), None) => start.elapsed(),
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    // the total time elapsed each time another SPLIT_LINES lines were cleared
+    pub fn splits(&self) -> &[Duration] {
+        &self.splits
+    }
+
+    pub fn pps(&self) -> f64 {
+        self.pieces as f64 / self.elapsed().as_secs_f64().max(1.0)
+    }
+
+    pub fn kpp(&self) -> f64 {
+        self.keys as f64 / self.pieces.max(1) as f64
+    }
+
+    // the contents of the stats panel, one entry per row
+    pub fn panel(&self) -> Vec<String> {
+        let width = STATS_DIMS.col;
+        let mut rows = vec![
+            stat_row("time", &format_time(self.elapsed()), width),
+            stat_row("pieces", &self.pieces.to_string(), width),
+            stat_row("pps", &format!("{:.2}", self.pps()), width),
+            stat_row("kpp", &format!("{:.2}", self.kpp()), width),
+            stat_row("faults", &self.faults.to_string(), width),
+        ];
+        for (name, count) in CLEAR_NAMES.iter().zip(self.clears.iter()) {
+            rows.push(stat_row(name, &count.to_string(), width));
+        }
+        for pair in (1..=7).collect::<Vec<i16>>().chunks(2) {
+            rows.push(
+                pair.iter()
+                    .map(|num| {
+                        format!(
+                            "{:?} {:<4}",
+                            num_to_shape(*num),
+                            self.shapes[*num as usize - 1]
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("  "),
+            );
+        }
+        rows
+    }
+}
+
+fn stat_row(label: &str, value: &str, width: usize) -> String {
+    format!("{}{:>2$}", label, value, width - label.len())
+}
+
+pub fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}.{:03}",
+        millis / 60000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+// separates thousands with commas, falling back to an abbreviation when that doesn't fit.
+// abbreviations are truncated rather than rounded, so they never grow an extra digit
+pub fn format_stat(stat: u64, width: usize) -> String {
+    let digits = stat.to_string();
+    let mut separated = String::new();
+    for (idx, chr) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            separated.push(',');
+        }
+        separated.push(chr);
+    }
+    if separated.len() <= width {
+        return separated;
+    }
+
+    for (idx, suffix) in ["k", "M", "B", "T", "Q"].iter().enumerate() {
+        let scaled = stat as f64 / 1000f64.powi(idx as i32 + 1);
+        if scaled >= 1000.0 {
+            continue;
+        }
+        for precision in (0..=2).rev() {
+            let factor = 10f64.powi(precision as i32);
+            let truncated = (scaled * factor).floor() / factor;
+            let abbreviated = format!("{:.*}{}", precision, truncated, suffix);
+            if abbreviated.len() <= width {
+                return abbreviated;
+            }
+        }
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separates_thousands() {
+        assert_eq!(format_stat(0, 7), "0");
+        assert_eq!(format_stat(999, 7), "999");
+        assert_eq!(format_stat(1_000, 7), "1,000");
+        assert_eq!(format_stat(999_999, 7), "999,999");
+    }
+
+    #[test]
+    fn abbreviates_when_too_wide() {
+        assert_eq!(format_stat(1_000_000, 7), "1.00M");
+        assert_eq!(format_stat(1_234_567, 7), "1.23M");
+        assert_eq!(format_stat(123_456_789, 7), "123.45M");
+    }
+
+    #[test]
+    fn never_rounds_up_past_the_width() {
+        assert_eq!(format_stat(9_999_999, 7), "9.99M");
+        assert_eq!(format_stat(999_999_999, 7), "999.99M");
+        for stat in [9_999_999, 99_999_999, 999_999_999, 999_999_999_999] {
+            assert!(format_stat(stat, 7).len() <= 7, "{}", stat);
+        }
+    }
+}